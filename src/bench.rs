@@ -0,0 +1,114 @@
+//! `--bench-draw`: time drawing one scene per ellipse and as a single batched
+//! mesh, side by side and without a window.
+//!
+//! Building covers what `Layer::display` does on the CPU. Rendering adds
+//! tessellation, upload and the GPU pass, waited on so that each frame is
+//! complete. It is skipped if no graphics adapter is available.
+
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use futures::executor::block_on;
+use log::*;
+use nannou::draw::{Renderer, RendererBuilder};
+use nannou::prelude::*;
+
+use crate::dot::{Nannou, fallback_bounds};
+use crate::layer::Layer;
+
+/// Size of the offscreen target, a common window size
+const TARGET_SIZE: [u32; 2] = [1280, 720];
+
+/// Frames simulated first so dots have grown apart from their spawn sizes
+const WARM_UP: usize = 120;
+
+/// Synthetic frame time used while warming up, about 60 fps
+const STEP: Duration = Duration::from_micros(16_667);
+
+/// Offscreen target and renderer drawn into, as a window's frame would be
+struct Target {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    texture: wgpu::Texture,
+    renderer: Renderer,
+}
+
+impl Target {
+    fn new() -> Result<Self> {
+        let instance = wgpu::Instance::new(Default::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::DEFAULT_POWER_PREFERENCE,
+            ..Default::default()
+        })).context("No graphics adapter")?;
+        let info = adapter.get_info();
+        println!("Rendering on {} ({:?}, {:?})", info.name, info.device_type, info.backend);
+        let (device, queue) = block_on(adapter.request_device(&wgpu::default_device_descriptor(), None))
+            .context("Requesting a device")?;
+
+        let texture = wgpu::TextureBuilder::new()
+            .size(TARGET_SIZE)
+            .format(Frame::TEXTURE_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            .build(&device);
+        let renderer = RendererBuilder::new()
+            .build_from_texture_descriptor(&device, texture.descriptor());
+        Ok(Target { device, queue, texture, renderer })
+    }
+
+    /// Render `draw` and wait for the GPU to finish
+    fn render(&mut self, draw: &Draw) {
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        self.renderer.render_to_texture(&self.device, &mut encoder, draw, &self.texture);
+        self.queue.submit([encoder.finish()]);
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+/// Mean and median of some timings
+fn summary(times: &mut [Duration]) -> String {
+    if times.is_empty() {
+        return "n/a".into()
+    }
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    format!("mean {:?}, median {:?}", mean, times[times.len() / 2])
+}
+
+/// Fill `layer`, let it settle, then draw it `frames` times each way and print the timings
+pub fn run(layer: &mut Layer, frames: usize) {
+    let bounds = fallback_bounds();
+    let mut since_start = Duration::ZERO;
+    layer.fill();
+    for _ in 0..WARM_UP {
+        since_start += STEP;
+        layer.update_with_bounds(&Update { since_last: STEP, since_start }, bounds);
+    }
+    layer.restyle();
+
+    let mut target = Target::new()
+        .map_err(|e| warn!("{:#}, timing only the building of draw commands", e))
+        .ok();
+
+    println!("Drawing {} dots, {} frames each way", layer.dots.len(), frames);
+    for batched in [false, true] {
+        layer.settings.batch_draw = batched;
+        let (mut building, mut rendering) = (Vec::with_capacity(frames), Vec::with_capacity(frames));
+
+        for _ in 0..frames {
+            let draw = Draw::new();
+            let start = Instant::now();
+            layer.display(&draw);
+            building.push(start.elapsed());
+
+            match &mut target {
+                Some(target) => {
+                    target.render(&draw);
+                    rendering.push(start.elapsed());
+                }
+                None => drop(draw.drain_commands()),
+            }
+        }
+
+        let name = if batched { "Batched mesh" } else { "Per ellipse" };
+        println!("{}: build {}; build and render {}", name, summary(&mut building), summary(&mut rendering));
+    }
+}
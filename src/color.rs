@@ -0,0 +1,328 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use log::*;
+use nannou::{color, rand::Rng, prelude::*};
+use palette::{named, FromColor};
+use serde::{Deserialize, Serialize};
+
+pub type Rgb = Srgb<u8>;
+pub type Rgba = Srgba<u8>;
+
+fn as_nn(c: palette::rgb::Rgb<palette::encoding::Srgb, u8>) -> Rgb {
+    rgb(c.red, c.green, c.blue)
+}
+
+pub fn from_str(name: &str) -> Option<Rgb> {
+    named::from_str(name).map(as_nn)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Black,
+    DarkGray,
+    DimGray,
+    Honeydew,
+    SteelBlue,
+    SlateGray,
+    Silver,
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+impl From<Color> for Rgb {
+    fn from(c: Color) -> Self {
+        from_str(&c.to_string()).expect("Unknown color {}")
+    }
+}
+
+/// Parse `#RRGGBB` (the `#` is optional)
+pub fn parse_hex(s: &str) -> Option<Rgb> {
+    let s = s.trim().trim_start_matches('#');
+    // from_str_radix alone would also take a sign
+    if s.len() != 6 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None
+    }
+
+    let channel = |i: usize| s.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    Some(rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Parse `#RRGGBB` or `#RRGGBBAA`, opaque when alpha is left out
+pub fn parse_hex_rgba(s: &str) -> Option<Rgba> {
+    let s = s.trim().trim_start_matches('#');
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None
+    }
+    let (color, alpha) = match s.len() {
+        6 => (s, None),
+        8 => (s.get(..6)?, Some(s.get(6..)?)),
+        _ => return None,
+    };
+
+    let c = parse_hex(color)?;
+    let alpha = match alpha {
+        Some(a) => u8::from_str_radix(a, 16).ok()?,
+        None => 255,
+    };
+    Some(rgba(c.red, c.green, c.blue, alpha))
+}
+
+/// Read one hex color per line, skipping blank lines and warning about invalid ones
+pub fn load_palette(path: &Path) -> Result<Vec<Rgba>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Reading palette {}", path.display()))?;
+
+    let colors = text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| {
+            let color = parse_hex_rgba(line);
+            if color.is_none() {
+                warn!("{}:{}: skipping invalid color {:?}", path.display(), i + 1, line.trim());
+            }
+            color
+        })
+        .collect();
+    Ok(colors)
+}
+
+pub fn to_hex(c: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue)
+}
+
+/// Serde adapter storing colors as `#rrggbb` strings
+pub mod hex {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use super::{parse_hex, to_hex, Rgb};
+
+    pub fn serialize<S: Serializer>(c: &Rgb, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&to_hex(*c))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Rgb, D::Error> {
+        let s = String::deserialize(d)?;
+        parse_hex(&s).ok_or_else(|| de::Error::custom(format!("Invalid hex color {:?}", s)))
+    }
+}
+
+pub trait ColorExt<C> {
+    fn with_alpha<A>(&self, alpha: A) -> color::Alpha<C,A>;
+}
+
+impl ColorExt<Rgb> for Rgb {
+    fn with_alpha<A>(&self, alpha: A) -> color::Alpha<Rgb,A> {
+        color::Alpha {
+            color: *self,
+            alpha,
+        }
+    }
+}
+
+/// Relative luminance in `0..=1`, from linearized channels
+pub fn luminance(c: Rgb) -> f32 {
+    let linear = |v: u8| {
+        let v = v as f32 / 255.0;
+        if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(c.red) + 0.7152 * linear(c.green) + 0.0722 * linear(c.blue)
+}
+
+/// Same color with its alpha multiplied by `factor`
+pub fn scale_alpha(c: Rgba, factor: f32) -> Rgba {
+    rgba(c.red, c.green, c.blue, (c.alpha as f32 * factor.clamp(0.0, 1.0)) as u8)
+}
+
+/// Same color with its hue turned by `degrees`, keeping alpha
+pub fn rotate_hue(c: Rgba, degrees: f32) -> Rgba {
+    let base = palette::Srgb::new(c.red, c.green, c.blue).into_format::<f32>();
+    let mut hsv = palette::Hsv::from_color(base);
+    hsv.hue += degrees;
+
+    let rotated = as_nn(palette::Srgb::from_color(hsv).into_format());
+    rgba(rotated.red, rotated.green, rotated.blue, c.alpha)
+}
+
+/// Mix from `a` towards `b` by `t` in `0..=1`, alpha included
+pub fn blend(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    let t = t.clamp(0.0, 1.0);
+    let lerp = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    rgba(lerp(a.red, b.red), lerp(a.green, b.green), lerp(a.blue, b.blue), lerp(a.alpha, b.alpha))
+}
+
+/// Hues new tinted colors are drawn around, relative to the tint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Harmony {
+    /// Only the tint's own hue
+    #[default]
+    Mono,
+    /// The tint and its neighbours either side
+    Analogous,
+    /// The tint and the opposite hue
+    Complementary,
+    /// Three hues evenly spaced around the wheel
+    Triadic,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 4] = [Harmony::Mono, Harmony::Analogous, Harmony::Complementary, Harmony::Triadic];
+
+    /// Hue offsets in degrees from the base hue
+    pub fn offsets(&self) -> &'static [f32] {
+        match self {
+            Harmony::Mono => &[0.0],
+            Harmony::Analogous => &[-30.0, 0.0, 30.0],
+            Harmony::Complementary => &[0.0, 180.0],
+            Harmony::Triadic => &[0.0, 120.0, 240.0],
+        }
+    }
+}
+
+/// Same color with a different hue, in degrees
+pub fn with_hue(c: Rgb, hue: f32) -> Rgb {
+    let base = palette::Srgb::new(c.red, c.green, c.blue).into_format::<f32>();
+    let mut hsv = palette::Hsv::from_color(base);
+    hsv.hue = hue.into();
+    as_nn(palette::Srgb::from_color(hsv).into_format())
+}
+
+/// Largest shift in hue, in degrees, of tinted bubble colors
+const TINT_HUE_JITTER: f32 = 15.0;
+/// Largest shift in saturation and value of tinted bubble colors
+const TINT_JITTER: f32 = 0.2;
+
+/// Ranges the channels of random colors are drawn from, each `[lo, hi]`
+/// covering `lo` up to but excluding `hi`. Tinted colors only use `alpha`.
+///
+/// The defaults are the long-standing ones, which keep red low for a cool palette.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelRanges {
+    pub red: [u8; 2],
+    pub green: [u8; 2],
+    pub blue: [u8; 2],
+    pub alpha: [u8; 2],
+}
+
+impl Default for ChannelRanges {
+    fn default() -> Self {
+        ChannelRanges {
+            red: [0, 128],
+            green: [0, 255],
+            blue: [0, 255],
+            alpha: [128, 255],
+        }
+    }
+}
+
+/// Channel value in `[lo, hi)`, or `lo` if the range is empty
+fn sample_channel(rng: &mut impl Rng, [lo, hi]: [u8; 2]) -> u8 {
+    if lo < hi { rng.gen_range(lo..hi) } else { lo }
+}
+
+/// Random bubble color drawn from `rng`, so seeded runs reproduce their colors.
+///
+/// With a tint the hue stays near one of the harmony's hues around the tint's,
+/// and only saturation and value wander.
+pub fn random_color(rng: &mut impl Rng, tint: Option<(Rgb, Harmony)>, ranges: &ChannelRanges) -> Rgba {
+    let Some((tint, harmony)) = tint else {
+        return rgba(
+            sample_channel(rng, ranges.red),
+            sample_channel(rng, ranges.green),
+            sample_channel(rng, ranges.blue),
+            sample_channel(rng, ranges.alpha),
+        )
+    };
+
+    let base = palette::Srgb::new(tint.red, tint.green, tint.blue).into_format::<f32>();
+    let mut hsv = palette::Hsv::from_color(base);
+    let offsets = harmony.offsets();
+    hsv.hue += offsets[rng.gen_range(0..offsets.len())] + rng.gen_range(-TINT_HUE_JITTER..TINT_HUE_JITTER);
+    hsv.saturation = (hsv.saturation + rng.gen_range(-TINT_JITTER..TINT_JITTER)).clamp(0.0, 1.0);
+    hsv.value = (hsv.value + rng.gen_range(-TINT_JITTER..TINT_JITTER)).clamp(0.0, 1.0);
+
+    let c = as_nn(palette::Srgb::from_color(hsv).into_format());
+    rgba(c.red, c.green, c.blue, sample_channel(rng, ranges.alpha))
+}
+
+/// Samples of matplotlib's viridis colormap at even steps
+const VIRIDIS: [(u8, u8, u8); 9] = [
+    (68, 1, 84),
+    (71, 44, 122),
+    (59, 81, 139),
+    (44, 113, 142),
+    (33, 144, 141),
+    (39, 173, 129),
+    (92, 200, 99),
+    (170, 220, 50),
+    (253, 231, 37),
+];
+
+/// Viridis color for `t` in `0..=1`, interpolated between samples
+pub fn viridis(t: f32) -> Rgb {
+    let x = t.clamp(0.0, 1.0) * (VIRIDIS.len() - 1) as f32;
+    let i = (x as usize).min(VIRIDIS.len() - 2);
+    let f = x - i as f32;
+
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+    let (a, b) = (VIRIDIS[i], VIRIDIS[i + 1]);
+    rgb(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, SeedableRng};
+
+    fn colors(seed: u64, tint: Option<(Rgb, Harmony)>) -> Vec<Rgba> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..100).map(|_| random_color(&mut rng, tint, &ChannelRanges::default())).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_colors() {
+        let tint = Some((rgb(70, 130, 180), Harmony::Triadic));
+        for tint in [None, tint] {
+            assert_eq!(colors(11, tint), colors(11, tint));
+            assert_ne!(colors(11, tint), colors(12, tint));
+        }
+    }
+
+    #[test]
+    fn channels_stay_in_their_ranges() {
+        let ranges = ChannelRanges { red: [10, 20], green: [0, 255], blue: [200, 200], alpha: [128, 255] };
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..1000 {
+            let c = random_color(&mut rng, None, &ranges);
+            assert!((10..20).contains(&c.red));
+            assert_eq!(c.blue, 200);
+            assert!(c.alpha >= 128);
+        }
+    }
+
+    #[test]
+    fn hex_colors_parse_with_and_without_alpha() {
+        assert_eq!(parse_hex_rgba("#ff8000"), Some(rgba(255, 128, 0, 255)));
+        assert_eq!(parse_hex_rgba(" 0a0B0c80 "), Some(rgba(10, 11, 12, 128)));
+    }
+
+    #[test]
+    fn malformed_hex_colors_are_rejected() {
+        for bad in ["", "#", "#fff", "#ff800", "#ff80000", "#ff8000801", "#gg8000", "#ff8000zz", "#ff80+0", "#ff8000+f"] {
+            assert_eq!(parse_hex_rgba(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn palettes_skip_blank_and_invalid_lines() {
+        let path = std::env::temp_dir().join(format!("nnndots-palette-{}.txt", std::process::id()));
+        fs::write(&path, "#112233\n\n   \nnot a color\n#44556677\n#12345\n").unwrap();
+        let palette = load_palette(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(palette.unwrap(), vec![rgba(0x11, 0x22, 0x33, 255), rgba(0x44, 0x55, 0x66, 0x77)]);
+    }
+}
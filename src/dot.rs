@@ -0,0 +1,904 @@
+use std::time::Duration;
+use nannou::{rand::Rng, prelude::*};
+use nannou::draw::{Drawing, primitive::{Primitive, polygon::SetPolygon}};
+use nannou::draw::properties::{ColorScalar, SetColor, SetStroke};
+use typed_builder::TypedBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{ColorExt, Rgba, blend, rotate_hue, scale_alpha, viridis};
+use crate::settings::OPTS;
+
+/// Lifecycle shared by everything on screen.
+///
+/// Keep this trait object-safe: layers drive their bubbles and particles
+/// through `&dyn Nannou` so new entity kinds only need an impl.
+pub trait Nannou {
+    fn display(&self, draw: &Draw);
+    fn update(&mut self, update: &Update);
+
+    /// Advance by one frame knowing the current window bounds, in world coordinates.
+    ///
+    /// These are the area the window covers with the view unpanned and unzoomed.
+    /// Panning and zooming only change what is shown, so arenas and spawns stay put.
+    ///
+    /// Entities that don't care about the window keep the default, which ignores them.
+    fn update_with_bounds(&mut self, update: &Update, _bounds: Rect) {
+        self.update(update);
+    }
+}
+
+// Fails to compile if `Nannou` stops being usable as a trait object
+const _: Option<&dyn Nannou> = None;
+
+pub type Point = Vec2;
+
+/// Nearest point to `point` inside `rect`
+pub fn clamp_into(rect: Rect, point: Point) -> Point {
+    point.clamp(rect.bottom_left(), rect.top_right())
+}
+
+/// Spawn area used until the window size is known
+pub fn fallback_bounds() -> Rect {
+    Rect::from_w_h(1000.0, 1000.0)
+}
+
+/// Uniform point within `bounds`, or the fallback area if `bounds` is empty
+pub fn rand_point(rng: &mut impl Rng, bounds: Rect) -> Point {
+    let bounds = if bounds.w() > 0.0 && bounds.h() > 0.0 { bounds } else { fallback_bounds() };
+    Point::new(rng.gen_range(bounds.left()..bounds.right()),
+               rng.gen_range(bounds.bottom()..bounds.top()))
+}
+
+/// Inner radius of the ring spawn region as a fraction of its outer radius
+const RING_INNER: f32 = 0.75;
+
+/// Largest per-axis stretch of a spawn region, enough for a band across a wide window
+pub const MAX_STRETCH: f32 = 4.0;
+
+/// Area new dots are spawned in, centered on the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SpawnRegion {
+    /// The window rectangle
+    #[default]
+    Square,
+    Disk,
+    /// Annulus just inside the disk's rim
+    Ring,
+}
+
+impl SpawnRegion {
+    pub const ALL: [SpawnRegion; 3] = [SpawnRegion::Square, SpawnRegion::Disk, SpawnRegion::Ring];
+
+    /// Area-uniform point in this region fitted to `bounds`, shrunk by `scale`
+    /// and then stretched by `stretch` along each axis, so disks become ellipses
+    pub fn sample(&self, rng: &mut impl Rng, bounds: Rect, scale: f32, stretch: Vec2) -> Point {
+        let bounds = if bounds.w() > 0.0 && bounds.h() > 0.0 { bounds } else { fallback_bounds() };
+        let scale = scale.clamp(0.01, 1.0);
+        let stretch = stretch.clamp(Vec2::splat(0.01), Vec2::splat(MAX_STRETCH));
+        let outer = bounds.w().min(bounds.h()) / 2.0 * scale;
+
+        match self {
+            SpawnRegion::Square => rand_point(rng, Rect::from_xy_wh(bounds.xy(), bounds.wh() * scale * stretch)),
+            SpawnRegion::Disk => bounds.xy() + rand_annulus(rng, 0.0, outer) * stretch,
+            SpawnRegion::Ring => bounds.xy() + rand_annulus(rng, outer * RING_INNER, outer) * stretch,
+        }
+    }
+}
+
+/// Area-uniform offset between two radii of the origin.
+///
+/// The squared radius is what's uniform over the area, so sampling the radius
+/// directly would crowd points towards the center.
+fn rand_annulus(rng: &mut impl Rng, inner: f32, outer: f32) -> Vec2 {
+    let r2 = rng.gen_range(inner * inner..=outer * outer);
+    let angle = rng.gen_range(0.0..TAU);
+    vec2(r2.sqrt(), 0.0).rotate(angle)
+}
+
+#[derive(Debug, Clone, Copy, TypedBuilder, Serialize, Deserialize)]
+pub struct Dot {
+    #[builder(setter(into))]
+    pub color: Rgba,
+    #[builder(setter(into), default)]
+    pub origin: Point,
+    #[builder(setter(into), default)]
+    pub pivot: Point,
+    #[builder(default=10.0)]
+    pub radius: f32,
+    #[builder(default=200.0)]
+    pub max_radius: f32,
+    /// Radius the dot was spawned with, where easing starts from
+    #[builder(default=radius)]
+    pub start_radius: f32,
+    /// Linear progress from `start_radius` to `max_radius` in `0..=1`
+    #[builder(default)]
+    pub progress: f32,
+    #[builder(default)]
+    pub easing: Easing,
+    #[builder(default=OPTS.rate)]
+    pub speed: f32,
+    /// Linear velocity carrying the whole orbit, in pixels per second
+    #[builder(setter(into), default)]
+    pub drift: Vec2,
+    /// Velocity of the pivot alone, so the orbit itself wanders. Zero keeps it fixed.
+    #[builder(setter(into), default)]
+    pub pivot_velocity: Vec2,
+    /// Radians per second `pivot_velocity` turns, making the pivot trace a slow second orbit
+    #[builder(default)]
+    pub pivot_turn: f32,
+    #[builder(default=OPTS.rate)]
+    pub growth_rate: f32,
+    #[builder(default=Duration::from_secs(0))]
+    pub age: Duration,
+    #[builder(default=Duration::from_secs(10))]
+    pub ttl: Duration,
+    /// Total time to live at spawn, for fading out as `ttl` runs down
+    #[builder(default=ttl)]
+    pub lifetime: Duration,
+    /// Shrinking away after a Clear instead of growing
+    #[builder(default)]
+    pub clearing: bool,
+    #[builder(default)]
+    pub phase: Phase,
+    /// Held in place by a click, neither moving, growing nor dying
+    #[builder(default)]
+    pub frozen: bool,
+    #[builder(default)]
+    pub shape: Shape,
+    /// Outline thickness in pixels, used when `stroke_color` is set
+    #[builder(default)]
+    pub stroke_weight: f32,
+    #[builder(default)]
+    pub stroke_color: Option<Rgba>,
+    /// Paint the interior, disabled together with a stroke for outline-only rings
+    #[builder(default=true)]
+    pub filled: bool,
+    /// Spawn sequence number within the layer, which keeps the draw order
+    /// stable however `dots` is rearranged
+    #[builder(default)]
+    #[serde(default)]
+    pub serial: u64,
+    /// Recomputed from the layer settings every frame, so not saved
+    #[builder(default)]
+    #[serde(skip)]
+    pub style: Style,
+}
+
+/// Stage of a dot's life
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Phase {
+    #[default]
+    Growing,
+    /// Reached its max radius and is drawn as a spreading ring until `POP_DURATION` passes
+    Popping { elapsed: Duration },
+}
+
+/// Length of the pop animation after a dot reaches its max radius
+pub const POP_DURATION: Duration = Duration::from_millis(200);
+
+/// How far the pop ring spreads beyond the dot, as a multiple of its radius
+const POP_SPREAD: f32 = 0.6;
+
+/// Render-time adjustments layered over a dot's stored appearance.
+///
+/// Refreshed from the layer settings every frame, so changing them
+/// restyles existing dots without touching their stored color.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Style {
+    /// How strongly growing towards `max_radius` fades a dot, 0 disables
+    pub alpha_by_radius: f32,
+    pub color_mode: ColorMode,
+    /// Speed mapped to the top of the colormap in `ColorMode::BySpeed`
+    pub max_speed: f32,
+    /// Degrees added to the hue of random colors
+    pub hue_shift: f32,
+    /// Multiplier on the segments ellipses are drawn with
+    pub ellipse_quality: f32,
+    /// Seconds new dots take to scale in from nothing, 0 shows them at once
+    pub spawn_in: f32,
+}
+
+/// Where a dot's hue comes from. Mapped modes keep the dot's random alpha.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// The color picked at spawn
+    #[default]
+    Random,
+    /// Orbital speed through viridis
+    BySpeed,
+    /// Current radius through viridis, changing as the dot grows
+    ByRadius,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 3] = [ColorMode::Random, ColorMode::BySpeed, ColorMode::ByRadius];
+}
+
+/// Largest max radius a dot may be spawned with
+pub const RADIUS_LIMIT: f32 = 512.0;
+
+/// Least opacity alpha-by-radius may fade a dot to
+const MIN_VISIBILITY: f32 = 0.1;
+
+/// Dots fade out over this final fraction of their lifetime
+const FADE_FRACTION: f32 = 0.25;
+
+/// Shrink rate in pixels per second for dots being cleared
+const CLEAR_RATE: f32 = 1000.0;
+
+/// Bounds on the segments of a single ellipse, however small or large
+const MIN_SEGMENTS: f32 = 12.0;
+const MAX_SEGMENTS: f32 = 256.0;
+
+/// Segments for an ellipse of visual radius `extent`.
+///
+/// Keeping each chord within about a quarter pixel of the true circle takes
+/// `π / acos(1 - 0.25 / r)` segments, roughly `π √(2r)`, so detail grows with
+/// the square root of the radius rather than its circumference.
+fn ellipse_resolution(extent: f32, quality: f32) -> f32 {
+    (quality * PI * (2.0 * extent.max(0.0)).sqrt()).clamp(MIN_SEGMENTS, MAX_SEGMENTS).round()
+}
+
+impl Nannou for Dot {
+    fn display(&self, draw: &Draw) {
+        if let Phase::Popping { elapsed } = self.phase {
+            let t = (elapsed.as_secs_f32() / POP_DURATION.as_secs_f32()).min(1.0);
+            let color = self.stroke_shade().unwrap_or_else(|| self.shade());
+            let diameter = self.radius * (1.0 + POP_SPREAD * t);
+            draw.ellipse()
+                .no_fill()
+                .stroke_weight(2.0)
+                .stroke_color(scale_alpha(color, 1.0 - t))
+                .resolution(ellipse_resolution(diameter / 2.0, self.style.ellipse_quality))
+                .w_h(diameter, diameter)
+                .xy(self.origin);
+            return
+        }
+
+        let size = self.drawn_size();
+        match self.shape {
+            Shape::Ellipse => {
+                self.paint(draw.ellipse()
+                    .resolution(ellipse_resolution(size / 2.0, self.style.ellipse_quality))
+                    .w(size)
+                    .h(size)
+                    .x_y(self.origin.x, self.origin.y));
+            },
+            Shape::Rect => {
+                self.paint(draw.rect()
+                    .w_h(size, size)
+                    .xy(self.origin));
+            },
+            Shape::Triangle => {
+                let p = self.shape.corners(size / 2.0)
+                    .map(|p| self.origin + p)
+                    .collect::<Vec<_>>();
+                self.paint(draw.tri().points(p[0], p[1], p[2]));
+            },
+        }
+
+        if self.frozen {
+            self.draw_highlight(draw);
+        }
+    }
+
+    fn update(&mut self, update: &Update) {
+        // A Clear still shrinks frozen dots away
+        if self.held() {
+            return
+        }
+
+        let delta = update.since_last;
+        self.ttl = self.ttl.checked_sub(delta).unwrap_or(Duration::ZERO);
+        self.age += delta;
+
+        let delta = delta.as_secs_f32();
+        if self.clearing {
+            self.radius = (self.radius - CLEAR_RATE * delta).max(0.0);
+        } else if let Phase::Popping { elapsed } = &mut self.phase {
+            *elapsed += update.since_last;
+        } else {
+            let span = self.max_radius - self.start_radius;
+            self.progress = if span > 0.0 {
+                (self.progress + self.growth_rate * delta / span).min(1.0)
+            } else {
+                1.0
+            };
+            // Land exactly on the max radius however long the frame, rather than
+            // wherever rounding of `start + span * 1.0` happens to put it
+            self.radius = if self.progress >= 1.0 {
+                self.max_radius
+            } else {
+                self.start_radius + span * self.easing.apply(self.progress)
+            };
+
+            if self.matured() {
+                self.phase = Phase::Popping { elapsed: Duration::ZERO };
+            }
+        }
+
+        self.translate(self.drift * delta);
+
+        // Move the pivot before placing the origin so the orbit radius holds
+        let offset = self.origin - self.pivot;
+        self.pivot_velocity = self.pivot_velocity.rotate(self.pivot_turn * delta);
+        self.pivot += self.pivot_velocity * delta;
+        let step = self.speed * delta;
+        self.origin = self.pivot + offset.rotate(step);
+    }
+}
+
+impl Dot {
+    /// Fill color to draw this frame after applying the style
+    pub fn shade(&self) -> Rgba {
+        let value = match self.style.color_mode {
+            ColorMode::Random => None,
+            ColorMode::BySpeed if self.style.max_speed > 0.0 => Some(self.speed.abs() / self.style.max_speed),
+            ColorMode::BySpeed => Some(0.0),
+            ColorMode::ByRadius => Some(self.radius / RADIUS_LIMIT),
+        };
+
+        let color = match value {
+            Some(t) => viridis(t).with_alpha(self.color.alpha),
+            None if self.style.hue_shift != 0.0 => rotate_hue(self.color, self.style.hue_shift),
+            None => self.color,
+        };
+        scale_alpha(color, self.fade())
+    }
+
+    /// Outline color to draw this frame, faded like the fill
+    pub fn stroke_shade(&self) -> Option<Rgba> {
+        self.stroke_color.map(|c| scale_alpha(c, self.fade()))
+    }
+
+    /// Opacity factor from the style and remaining lifetime
+    fn fade(&self) -> f32 {
+        let growth = if self.max_radius > 0.0 {
+            (self.radius / self.max_radius).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let fade = (1.0 - self.style.alpha_by_radius * growth).max(MIN_VISIBILITY);
+        fade * self.expiry()
+    }
+
+    /// Apply fill and stroke to a shape positioned by the caller
+    fn paint<T>(&self, drawing: Drawing<'_, T>)
+    where
+        T: SetColor<ColorScalar> + SetPolygon + SetStroke + Into<Primitive>,
+        Primitive: Into<Option<T>>,
+    {
+        let drawing = if self.filled {
+            drawing.color(self.shade())
+        } else {
+            drawing.no_fill()
+        };
+
+        if let Some(color) = self.stroke_shade() {
+            drawing.stroke_weight(self.stroke_weight).stroke_color(color);
+        }
+    }
+
+    /// Opacity factor falling from 1 to 0 over the last part of the lifetime
+    pub fn expiry(&self) -> f32 {
+        let lifetime = self.lifetime.as_secs_f32();
+        if lifetime <= 0.0 {
+            return 1.0
+        }
+
+        let remaining = self.ttl.as_secs_f32() / lifetime;
+        (remaining / FADE_FRACTION).min(1.0)
+    }
+
+    /// Finished growing towards `max_radius`
+    pub fn matured(&self) -> bool {
+        self.progress >= 1.0
+    }
+
+    /// Why these parameters don't make a drawable dot, if they don't.
+    ///
+    /// The builder accepts anything, which trusted code relies on. Dots built
+    /// from settings are checked with this before joining a layer.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.origin.is_finite() || !self.pivot.is_finite() {
+            return Err(format!("non-finite position {} around {}", self.origin, self.pivot))
+        }
+        if !self.max_radius.is_finite() || self.max_radius <= 0.0 {
+            return Err(format!("max radius {} must be positive", self.max_radius))
+        }
+        if self.radius.is_nan() || self.radius < 0.0 {
+            return Err(format!("radius {} must not be negative", self.radius))
+        }
+        if self.radius > self.max_radius {
+            return Err(format!("radius {} exceeds max radius {}", self.radius, self.max_radius))
+        }
+        if ![self.speed, self.growth_rate].iter().all(|v| v.is_finite()) || !self.drift.is_finite() {
+            return Err("non-finite speed, growth rate or drift".into())
+        }
+        Ok(())
+    }
+
+    /// Frozen and not being cleared
+    pub fn held(&self) -> bool {
+        self.frozen && !self.clearing
+    }
+
+    /// Outline marking a frozen dot
+    pub fn draw_highlight(&self, draw: &Draw) {
+        draw.ellipse()
+            .no_fill()
+            .stroke_weight(1.5)
+            .stroke_color(WHITE.with_alpha(0.6))
+            .resolution(ellipse_resolution(self.drawn_size() / 2.0 + 2.0, self.style.ellipse_quality))
+            .w_h(self.drawn_size() + 4.0, self.drawn_size() + 4.0)
+            .xy(self.origin);
+    }
+
+    /// Pop animation has finished playing
+    pub fn popped(&self) -> bool {
+        matches!(self.phase, Phase::Popping { elapsed } if elapsed >= POP_DURATION)
+    }
+
+    /// Visual radius on screen, since `radius` is drawn as the ellipse diameter
+    pub fn extent(&self) -> f32 {
+        self.radius / 2.0
+    }
+
+    /// Diameter to draw at, eased up from nothing while the dot is younger than
+    /// the style's spawn-in time. Only rendering sees this, `radius` grows as usual.
+    pub fn drawn_size(&self) -> f32 {
+        let spawn_in = self.style.spawn_in;
+        let age = self.age.as_secs_f32();
+        if spawn_in > 0.0 && age < spawn_in {
+            self.radius * Easing::EaseOut.apply(age / spawn_in)
+        } else {
+            self.radius
+        }
+    }
+
+    /// Move the dot together with its pivot, preserving the orbit
+    pub fn translate(&mut self, shift: Vec2) {
+        self.origin += shift;
+        self.pivot += shift;
+    }
+
+    /// Take in `other`, moving to their area-weighted centroid and growing to
+    /// their combined area. Growth restarts from the merged size.
+    ///
+    /// The max radius never falls below the merged radius, up to `RADIUS_LIMIT`,
+    /// so a merge past both maxima stays at full size instead of snapping back.
+    pub fn absorb(&mut self, other: &Dot) {
+        let (a, b) = (self.radius.powi(2), other.radius.powi(2));
+        let total = a + b;
+        if total <= 0.0 {
+            return
+        }
+
+        let centroid = (self.origin * a + other.origin * b) / total;
+        self.translate(centroid - self.origin);
+        self.max_radius = self.max_radius.max(other.max_radius).max(total.sqrt()).min(RADIUS_LIMIT);
+        self.radius = total.sqrt().min(self.max_radius);
+        self.start_radius = self.radius;
+        self.progress = 0.0;
+        self.color = blend(self.color, other.color, b / total);
+    }
+
+    /// Shift the whole orbit so the dot's center lies within `rect`
+    pub fn nudge_into(&mut self, rect: Rect) {
+        self.translate(clamp_into(rect, self.origin) - self.origin);
+    }
+
+    /// Instantaneous velocity, orbiting the pivot plus any drift and pivot wander
+    pub fn velocity(&self) -> Vec2 {
+        self.orbital_velocity() + self.drift + self.pivot_velocity
+    }
+
+    fn orbital_velocity(&self) -> Vec2 {
+        self.speed * (self.origin - self.pivot).perp()
+    }
+
+    /// Reflect the motion off a wall with the given outward normal.
+    ///
+    /// Drift and pivot wander are reflected directly. The pivot is moved so the orbit continues
+    /// with the reflected velocity, preserving both speed and orbit radius.
+    pub fn bounce(&mut self, normal: Vec2) {
+        if self.drift.dot(normal) > 0.0 {
+            self.drift -= 2.0 * self.drift.dot(normal) * normal;
+        }
+        if self.pivot_velocity.dot(normal) > 0.0 {
+            self.pivot_velocity -= 2.0 * self.pivot_velocity.dot(normal) * normal;
+        }
+
+        let v = self.orbital_velocity();
+        if self.speed == 0.0 || v.dot(normal) <= 0.0 {
+            return
+        }
+
+        let v = v - 2.0 * v.dot(normal) * normal;
+        let offset = -(v / self.speed).perp();
+        self.pivot = self.origin - offset;
+    }
+}
+
+/// Growth profile from a dot's starting radius to its maximum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    Elastic,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::Elastic];
+
+    /// Map linear progress `t` in `0..=1` onto the eased curve
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::Elastic if t == 0.0 || t == 1.0 => t,
+            Easing::Elastic => {
+                const C4: f32 = TAU / 3.0;
+                2.0.powf(-10.0 * t) * ((t * 10.0 - 0.75) * C4).sin() + 1.0
+            },
+        }
+    }
+}
+
+/// Number of segments used to tessellate each ellipse in batch mode
+const MESH_SEGMENTS: usize = 32;
+
+/// Outline drawn for a dot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    #[default]
+    Ellipse,
+    /// Axis-aligned square with sides equal to the ellipse diameter
+    Rect,
+    /// Equilateral triangle, point up, inscribed in the ellipse
+    Triangle,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Ellipse, Shape::Rect, Shape::Triangle];
+
+    /// Polygon corners around the origin for a dot of the given extent,
+    /// used to tessellate every shape the same way.
+    pub fn corners(&self, extent: f32) -> impl Iterator<Item = Vec2> {
+        let (count, start, reach) = match self {
+            Shape::Ellipse => (MESH_SEGMENTS, 0.0, extent),
+            Shape::Rect => (4, PI / 4.0, extent * std::f32::consts::SQRT_2),
+            Shape::Triangle => (3, 0.0, extent),
+        };
+
+        let rim = vec2(0.0, reach).rotate(start);
+        (0..count).map(move |i| rim.rotate(i as f32 / count as f32 * TAU))
+    }
+}
+
+/// Which shapes a layer may pick from when spawning
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ShapePool {
+    pub ellipse: bool,
+    pub rect: bool,
+    pub triangle: bool,
+}
+
+impl Default for ShapePool {
+    fn default() -> Self {
+        ShapePool {
+            ellipse: true,
+            rect: false,
+            triangle: false,
+        }
+    }
+}
+
+impl ShapePool {
+    pub fn allows(&self, shape: Shape) -> bool {
+        match shape {
+            Shape::Ellipse => self.ellipse,
+            Shape::Rect => self.rect,
+            Shape::Triangle => self.triangle,
+        }
+    }
+
+    pub fn allows_mut(&mut self, shape: Shape) -> &mut bool {
+        match shape {
+            Shape::Ellipse => &mut self.ellipse,
+            Shape::Rect => &mut self.rect,
+            Shape::Triangle => &mut self.triangle,
+        }
+    }
+
+    /// Uniform pick among the allowed shapes, ellipses if none are
+    pub fn choose(&self, rng: &mut impl Rng) -> Shape {
+        let allowed = || Shape::ALL.into_iter().filter(|&s| self.allows(s));
+        match allowed().count() {
+            0 => Shape::Ellipse,
+            n => allowed().nth(rng.gen_range(0..n)).unwrap_or_default(),
+        }
+    }
+}
+
+/// Shared vertex and index buffers for drawing all bubbles in a single mesh
+#[derive(Default)]
+pub struct BubbleMesh {
+    points: Vec<(Vec3, Rgba)>,
+    indices: Vec<usize>,
+}
+
+impl BubbleMesh {
+    /// Append a triangle fan approximating the dot's shape, plus its outline
+    pub fn push(&mut self, dot: &Dot) {
+        let corners = dot.shape.corners(dot.drawn_size() / 2.0).collect::<Vec<_>>();
+        let count = corners.len();
+
+        if dot.filled {
+            let center = self.points.len();
+            let color = dot.shade();
+            self.points.push((dot.origin.extend(0.0), color));
+            self.points.extend(corners.iter().map(|&p| ((dot.origin + p).extend(0.0), color)));
+
+            self.indices.extend((0..count).flat_map(|i| {
+                [center, center + 1 + i, center + 1 + (i + 1) % count]
+            }));
+        }
+
+        // Ring of quads straddling the rim, half the weight either side
+        if let Some(color) = dot.stroke_shade() {
+            let start = self.points.len();
+            let half = dot.stroke_weight / 2.0;
+            self.points.extend(corners.iter().flat_map(|&p| {
+                let dir = p.normalize_or_zero();
+                [p - dir * half, p + dir * half]
+                    .map(|q| ((dot.origin + q).extend(0.0), color))
+            }));
+
+            self.indices.extend((0..count).flat_map(|i| {
+                let (inner, outer) = (start + 2 * i, start + 2 * i + 1);
+                let j = (i + 1) % count;
+                let (next_inner, next_outer) = (start + 2 * j, start + 2 * j + 1);
+                [inner, outer, next_outer, inner, next_outer, next_inner]
+            }));
+        }
+    }
+
+    pub fn display(self, draw: &Draw) {
+        if !self.indices.is_empty() {
+            draw.mesh().indexed_colored(self.points, self.indices);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArenaShape {
+    /// No boundary besides the window itself
+    Rect,
+    /// Centered circle that bubbles bounce around inside
+    Circle,
+    /// Bubbles leaving one edge of the window reappear at the opposite edge
+    Wrap,
+    /// Bubbles reflect off the window edges as soon as their rim touches one
+    Bounce,
+}
+
+impl ArenaShape {
+    pub fn constrain(&self, dot: &mut Dot, arena_radius: f32, window: Rect) {
+        match self {
+            ArenaShape::Rect => {},
+            ArenaShape::Circle => {
+                let limit = (arena_radius - dot.extent()).max(0.0);
+                if dot.origin.length() > limit {
+                    let normal = dot.origin.normalize_or_zero();
+                    dot.translate(normal * limit - dot.origin);
+                    dot.bounce(normal);
+                }
+            },
+            ArenaShape::Wrap => {
+                // Wait until the dot is fully out of view, then carry its whole orbit across
+                let bounds = window.pad(-dot.extent());
+                let wrap = |v: f32, lo: f32, hi: f32| {
+                    let span = hi - lo;
+                    if span <= 0.0 || (lo..=hi).contains(&v) {
+                        0.0
+                    } else {
+                        (v - lo).rem_euclid(span) + lo - v
+                    }
+                };
+                let shift = vec2(wrap(dot.origin.x, bounds.left(), bounds.right()),
+                                 wrap(dot.origin.y, bounds.bottom(), bounds.top()));
+                dot.translate(shift);
+            },
+            ArenaShape::Bounce => {
+                let bounds = window.pad(dot.extent());
+                let walls = [
+                    (dot.origin.x < bounds.left(), vec2(-1.0, 0.0), vec2(bounds.left() - dot.origin.x, 0.0)),
+                    (dot.origin.x > bounds.right(), vec2(1.0, 0.0), vec2(bounds.right() - dot.origin.x, 0.0)),
+                    (dot.origin.y < bounds.bottom(), vec2(0.0, -1.0), vec2(0.0, bounds.bottom() - dot.origin.y)),
+                    (dot.origin.y > bounds.top(), vec2(0.0, 1.0), vec2(0.0, bounds.top() - dot.origin.y)),
+                ];
+                // Bubbles larger than the window are left to drift rather than jitter
+                for (crossed, normal, correction) in walls {
+                    if crossed && bounds.w() > 0.0 && bounds.h() > 0.0 {
+                        dot.translate(correction);
+                        dot.bounce(normal);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot() -> Dot {
+        Dot::builder()
+            .color(rgba(0u8, 0, 0, 255))
+            .origin(vec2(10.0, -20.0))
+            .pivot(vec2(0.0, 0.0))
+            .radius(10.0)
+            .max_radius(50.0)
+            .build()
+    }
+
+    #[test]
+    fn clamp_into_keeps_points_inside() {
+        let rect = Rect::from_corners(vec2(-100.0, -50.0), vec2(100.0, 50.0));
+        for p in [vec2(0.0, 0.0), vec2(-99.5, 49.5), vec2(30.0, -20.0)] {
+            assert_eq!(clamp_into(rect, p), p);
+        }
+    }
+
+    #[test]
+    fn clamp_into_keeps_points_on_the_edge() {
+        let rect = Rect::from_corners(vec2(-100.0, -50.0), vec2(100.0, 50.0));
+        for p in [vec2(-100.0, 0.0), vec2(100.0, 50.0), vec2(20.0, -50.0)] {
+            assert_eq!(clamp_into(rect, p), p);
+        }
+    }
+
+    #[test]
+    fn clamp_into_moves_outside_points_to_the_nearest_edge() {
+        let rect = Rect::from_corners(vec2(-100.0, -50.0), vec2(100.0, 50.0));
+        assert_eq!(clamp_into(rect, vec2(150.0, 10.0)), vec2(100.0, 10.0));
+        assert_eq!(clamp_into(rect, vec2(-20.0, -80.0)), vec2(-20.0, -50.0));
+        assert_eq!(clamp_into(rect, vec2(-300.0, 300.0)), vec2(-100.0, 50.0));
+    }
+
+    #[test]
+    fn validate_accepts_sampled_parameters() {
+        assert_eq!(dot().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_radius_past_max() {
+        let d = Dot { radius: 60.0, ..dot() };
+        assert_eq!(d.validate(), Err("radius 60 exceeds max radius 50".into()));
+    }
+
+    #[test]
+    fn validate_rejects_negative_radius() {
+        let d = Dot { radius: -1.0, ..dot() };
+        assert_eq!(d.validate(), Err("radius -1 must not be negative".into()));
+    }
+
+    #[test]
+    fn validate_rejects_non_finite_positions() {
+        for (origin, pivot) in [(vec2(f32::NAN, 0.0), Vec2::ZERO), (Vec2::ZERO, vec2(0.0, f32::INFINITY))] {
+            let d = Dot { origin, pivot, ..dot() };
+            assert_eq!(d.validate(), Err(format!("non-finite position {} around {}", origin, pivot)));
+        }
+    }
+
+    #[test]
+    fn validate_rejects_non_positive_max_radius() {
+        for max_radius in [0.0, -5.0, f32::NAN] {
+            let d = Dot { radius: 0.0, max_radius, ..dot() };
+            assert_eq!(d.validate(), Err(format!("max radius {} must be positive", max_radius)));
+        }
+    }
+
+    #[test]
+    fn absorb_conserves_area() {
+        let mut a = Dot { origin: vec2(0.0, 0.0), radius: 30.0, ..dot() };
+        let b = Dot { origin: vec2(40.0, 0.0), radius: 40.0, ..dot() };
+        a.absorb(&b);
+
+        assert!((a.radius - 50.0).abs() < 1e-4);
+        // Weights 900 and 1600 out of 2500 put the centroid at 40 * 0.64
+        assert!(a.origin.distance(vec2(25.6, 0.0)) < 1e-4);
+        assert_eq!(a.max_radius, 50.0);
+    }
+
+    #[test]
+    fn absorb_past_both_maxima_keeps_its_size() {
+        let mut a = Dot { radius: 40.0, max_radius: 45.0, ..dot() };
+        let b = Dot { radius: 30.0, max_radius: 35.0, ..dot() };
+        a.absorb(&b);
+        assert_eq!(a.max_radius, a.radius);
+
+        a.update(&Update { since_last: Duration::from_millis(16), since_start: Duration::ZERO });
+        assert!((a.radius - 50.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn absorb_stops_at_the_radius_limit() {
+        let mut a = Dot { radius: 400.0, max_radius: RADIUS_LIMIT, ..dot() };
+        let b = Dot { radius: 400.0, max_radius: RADIUS_LIMIT, ..dot() };
+        a.absorb(&b);
+        assert_eq!(a.radius, RADIUS_LIMIT);
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn disk_samples_are_area_uniform() {
+        use nannou::rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(1);
+        let (n, outer) = (20_000, 100.0);
+        let samples = (0..n).map(|_| rand_annulus(&mut rng, 0.0, outer)).collect::<Vec<_>>();
+        assert!(samples.iter().all(|p| p.length() <= outer + 1e-3));
+
+        // Half the area lies within 1/√2 of the radius; sampling the radius
+        // itself uniformly would put about 71% of points there instead
+        let inner = samples.iter().filter(|p| p.length() < outer / 2f32.sqrt()).count();
+        let fraction = inner as f32 / n as f32;
+        assert!((fraction - 0.5).abs() < 0.02, "{} of samples in the inner half of the area", fraction);
+    }
+
+    #[test]
+    fn ring_samples_stay_in_the_annulus() {
+        use nannou::rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..5_000 {
+            let r = rand_annulus(&mut rng, 75.0, 100.0).length();
+            assert!((75.0 - 1e-3..=100.0 + 1e-3).contains(&r));
+        }
+    }
+
+    #[test]
+    fn circle_arena_holds_dots_inside() {
+        let window = Rect::from_w_h(1000.0, 1000.0);
+        let mut d = Dot { origin: vec2(300.0, 0.0), pivot: vec2(300.0, 0.0), radius: 40.0, drift: vec2(50.0, 10.0), ..dot() };
+        ArenaShape::Circle.constrain(&mut d, 200.0, window);
+
+        assert!((d.origin.length() - 180.0).abs() < 1e-3);
+        assert_eq!(d.drift, vec2(-50.0, 10.0));
+    }
+
+    #[test]
+    fn wrap_arena_carries_dots_across_once_out_of_view() {
+        let window = Rect::from_w_h(400.0, 400.0);
+        // Drawn 20 across, so still overlapping the edge at 200
+        let mut d = Dot { origin: vec2(205.0, 0.0), pivot: vec2(205.0, 0.0), radius: 20.0, ..dot() };
+        ArenaShape::Wrap.constrain(&mut d, 0.0, window);
+        assert_eq!(d.origin, vec2(205.0, 0.0));
+
+        d.translate(vec2(10.0, 0.0));
+        ArenaShape::Wrap.constrain(&mut d, 0.0, window);
+        // Fully out of view at 215, and carried to just out of view on the far side
+        assert!((d.origin.x - -205.0).abs() < 1e-3);
+        assert_eq!(d.origin, d.pivot);
+    }
+
+    #[test]
+    fn bounce_arena_reflects_off_the_edge() {
+        let window = Rect::from_w_h(400.0, 400.0);
+        let mut d = Dot { origin: vec2(0.0, -195.0), pivot: vec2(0.0, -195.0), radius: 20.0, drift: vec2(5.0, -30.0), ..dot() };
+        ArenaShape::Bounce.constrain(&mut d, 0.0, window);
+
+        assert!((d.origin.y - -190.0).abs() < 1e-3);
+        assert_eq!(d.drift, vec2(5.0, 30.0));
+
+        // Exactly touching the edge is left alone
+        let mut d = Dot { origin: vec2(190.0, 0.0), pivot: vec2(190.0, 0.0), radius: 20.0, drift: vec2(30.0, 0.0), ..dot() };
+        ArenaShape::Bounce.constrain(&mut d, 0.0, window);
+        assert_eq!(d.drift, vec2(30.0, 0.0));
+    }
+}
@@ -0,0 +1,126 @@
+//! Uniform grid bucketing circles by the cells they overlap, to find bodies near
+//! a point or near each other without scanning them all. It only knows about
+//! centers and radii, so it works on any population, not just dots.
+
+use std::collections::HashMap;
+use nannou::glam::Vec2;
+
+/// Narrowest cell allowed, so a population of points doesn't make a cell per body
+const MIN_CELL: f32 = 1.0;
+
+type Cell = (i32, i32);
+
+/// Body indices bucketed into square cells at least one diameter of the
+/// largest body wide, so each body straddles at most four cells.
+///
+/// Queries return candidates: every body that could touch the query, and
+/// possibly some that don't. Callers still check the exact distance.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    cell: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+    /// Center and radius of each body, by index
+    bodies: Vec<(Vec2, f32)>,
+}
+
+impl SpatialGrid {
+    /// Bucket bodies given as center and radius, indexed in iteration order
+    pub fn new(bodies: impl IntoIterator<Item = (Vec2, f32)>) -> Self {
+        let bodies = bodies.into_iter().collect::<Vec<_>>();
+        let widest = bodies.iter().map(|&(_, r)| 2.0 * r).fold(0.0, f32::max);
+        let mut grid = SpatialGrid {
+            cell: widest.max(MIN_CELL),
+            cells: HashMap::new(),
+            bodies,
+        };
+
+        for i in 0..grid.bodies.len() {
+            for cell in grid.covered(i) {
+                grid.cells.entry(cell).or_default().push(i);
+            }
+        }
+        grid
+    }
+
+    fn cell_of(&self, p: Vec2) -> Cell {
+        ((p.x / self.cell).floor() as i32, (p.y / self.cell).floor() as i32)
+    }
+
+    /// Cells overlapped by the bounding square of body `i`
+    fn covered(&self, i: usize) -> Vec<Cell> {
+        let (center, radius) = self.bodies[i];
+        // Skip bodies that can't be placed, like those at NaN positions
+        if !center.is_finite() || !radius.is_finite() {
+            return Vec::new()
+        }
+
+        let (x0, y0) = self.cell_of(center - Vec2::splat(radius));
+        let (x1, y1) = self.cell_of(center + Vec2::splat(radius));
+        (x0..=x1).flat_map(|x| (y0..=y1).map(move |y| (x, y))).collect()
+    }
+
+    /// Bodies that might contain `p`
+    pub fn query_point(&self, p: Vec2) -> impl Iterator<Item = usize> + '_ {
+        self.cells.get(&self.cell_of(p)).into_iter().flatten().copied()
+    }
+
+    /// Bodies other than `i` that might overlap it, each listed once
+    pub fn query_neighbors(&self, i: usize) -> Vec<usize> {
+        let mut found = self.covered(i).into_iter()
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|&j| j != i)
+            .collect::<Vec<_>>();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn bodies(seed: u64) -> Vec<(Vec2, f32)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..300)
+            .map(|_| (Vec2::new(rng.gen_range(-500.0..500.0), rng.gen_range(-500.0..500.0)), rng.gen_range(0.0..40.0)))
+            .collect()
+    }
+
+    #[test]
+    fn neighbors_cover_brute_force_overlaps() {
+        for seed in 0..5 {
+            let bodies = bodies(seed);
+            let grid = SpatialGrid::new(bodies.iter().copied());
+
+            for (i, &(a, ra)) in bodies.iter().enumerate() {
+                let found = grid.query_neighbors(i);
+                for (j, &(b, rb)) in bodies.iter().enumerate() {
+                    if i != j && a.distance(b) < ra + rb {
+                        assert!(found.contains(&j), "seed {}: {} overlaps {} but wasn't a candidate", seed, i, j);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn point_query_covers_containing_bodies() {
+        let bodies = bodies(42);
+        let grid = SpatialGrid::new(bodies.iter().copied());
+        let mut rng = StdRng::seed_from_u64(43);
+
+        for _ in 0..1000 {
+            let p = Vec2::new(rng.gen_range(-500.0..500.0), rng.gen_range(-500.0..500.0));
+            let found = grid.query_point(p).collect::<Vec<_>>();
+            for (i, &(center, radius)) in bodies.iter().enumerate() {
+                if center.distance(p) < radius {
+                    assert!(found.contains(&i), "{} contains {} but wasn't a candidate", i, p);
+                }
+            }
+        }
+    }
+}
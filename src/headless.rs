@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+use nannou::prelude::*;
+
+use crate::dot::{Nannou, fallback_bounds};
+use crate::layer::Layer;
+use crate::settings::OPTS;
+use crate::stats::StatsLog;
+
+/// Synthetic frame time fed to every simulated update, about 60 fps
+const STEP: Duration = Duration::from_micros(16_667);
+
+/// Step the layers `frames` times without a window or UI and print timing stats
+pub fn run(layers: &mut [Layer], frames: usize) {
+    let bounds = fallback_bounds();
+    let mut times = Vec::with_capacity(frames);
+    let mut since_start = Duration::ZERO;
+    let mut stats_log = StatsLog::new(OPTS.stats_interval);
+
+    for _ in 0..frames {
+        since_start += STEP;
+        let update = Update { since_last: STEP, since_start };
+
+        let start = Instant::now();
+        for layer in layers.iter_mut() {
+            layer.approach_cap(layer.settings.max_dots(bounds), STEP.as_secs_f32());
+            layer.restyle();
+            layer.update_with_bounds(&update, bounds);
+        }
+        times.push(start.elapsed());
+        stats_log.tick(STEP, layers);
+    }
+
+    let total: Duration = times.iter().sum();
+    times.sort();
+    let percentile = |p: usize| times.get((times.len() * p / 100).min(times.len().saturating_sub(1)))
+        .copied()
+        .unwrap_or_default();
+
+    println!("Simulated {} frames in {:?}", frames, total);
+    if !times.is_empty() {
+        println!("Per frame: mean {:?}, median {:?}, p99 {:?}, max {:?}",
+                 total / times.len() as u32, percentile(50), percentile(99), times[times.len() - 1]);
+    }
+    for (i, layer) in layers.iter().enumerate() {
+        println!("Layer {}: {} dots", i + 1, layer.dots.len());
+    }
+}
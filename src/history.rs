@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::dot::Dot;
+use crate::layer::Layer;
+
+/// Record a snapshot every this many simulated frames
+const SNAPSHOT_INTERVAL: usize = 10;
+
+/// Memory allowed for snapshots before the oldest are discarded
+const BUDGET_BYTES: usize = 64 * 1024 * 1024;
+
+/// Ring buffer of recent dot populations for scrubbing back in time.
+///
+/// Each snapshot costs `size_of::<Dot>()` per live dot, summed over layers,
+/// and is charged against the 64 MiB budget. The window of history shrinks in
+/// proportion to the population: doubling the dots halves how far back it reaches.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Dots of every layer, oldest snapshot first
+    frames: VecDeque<Vec<Vec<Dot>>>,
+    bytes: usize,
+    frame: usize,
+    /// How many snapshots back from the newest is currently shown
+    pub offset: usize,
+}
+
+fn snapshot_bytes(snapshot: &[Vec<Dot>]) -> usize {
+    snapshot.iter().map(|dots| dots.len() * size_of::<Dot>()).sum()
+}
+
+impl History {
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Called once per simulated frame, keeping every Nth state
+    pub fn record(&mut self, layers: &[Layer]) {
+        self.frame += 1;
+        if !self.frame.is_multiple_of(SNAPSHOT_INTERVAL) {
+            return
+        }
+
+        // Resuming after a rewind forks history, so drop the abandoned future
+        for _ in 0..self.offset {
+            if let Some(s) = self.frames.pop_back() {
+                self.bytes -= snapshot_bytes(&s);
+            }
+        }
+        self.offset = 0;
+
+        let snapshot = layers.iter().map(|l| l.dots.clone()).collect::<Vec<_>>();
+        self.bytes += snapshot_bytes(&snapshot);
+        self.frames.push_back(snapshot);
+
+        while self.bytes > BUDGET_BYTES {
+            match self.frames.pop_front() {
+                Some(s) => self.bytes -= snapshot_bytes(&s),
+                None => break,
+            }
+        }
+    }
+
+    /// Replace the layers' dots with the snapshot `offset` steps back from the newest
+    pub fn restore(&mut self, offset: usize, layers: &mut [Layer]) {
+        let Some(index) = self.frames.len().checked_sub(offset + 1) else {
+            return
+        };
+
+        self.offset = offset;
+        for (layer, dots) in layers.iter_mut().zip(&self.frames[index]) {
+            layer.dots.clone_from(dots);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, SeedableRng};
+    use crate::settings::Settings;
+
+    #[test]
+    fn snapshots_are_charged_per_dot() {
+        let mut layer = Layer::new(Settings { max_count: 100, ..Settings::default() }, StdRng::seed_from_u64(1));
+        layer.fill();
+        let layers = [layer];
+
+        let mut history = History::default();
+        for _ in 0..SNAPSHOT_INTERVAL * 3 {
+            history.record(&layers);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.bytes, 3 * 100 * size_of::<Dot>());
+    }
+}
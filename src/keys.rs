@@ -0,0 +1,44 @@
+use nannou::winit::event::VirtualKeyCode;
+
+/// Something a keyboard shortcut does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    TogglePause,
+    Clear,
+    Dump,
+    Burst,
+    Screenshot,
+    ToggleHelp,
+    Step,
+}
+
+impl Command {
+    /// One line for the help window
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::TogglePause => "Pause or resume the simulation",
+            Command::Clear => "Shrink every dot away",
+            Command::Dump => "Print debugging data and save the config",
+            Command::Burst => "Fill the active layer up to its cap at once",
+            Command::Screenshot => "Save the next frame as a PNG",
+            Command::ToggleHelp => "Show or hide this help",
+            Command::Step => "Advance a single frame while paused",
+        }
+    }
+}
+
+/// Every keyboard shortcut, dispatched and listed in the help from this one table
+pub const BINDINGS: &[(VirtualKeyCode, Command)] = &[
+    (VirtualKeyCode::Space, Command::TogglePause),
+    (VirtualKeyCode::B, Command::Burst),
+    (VirtualKeyCode::C, Command::Clear),
+    (VirtualKeyCode::D, Command::Dump),
+    (VirtualKeyCode::H, Command::ToggleHelp),
+    (VirtualKeyCode::S, Command::Screenshot),
+    (VirtualKeyCode::Period, Command::Step),
+];
+
+/// Command bound to `key`, if any
+pub fn command(key: VirtualKeyCode) -> Option<Command> {
+    BINDINGS.iter().find(|&&(k, _)| k == key).map(|&(_, c)| c)
+}
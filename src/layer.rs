@@ -0,0 +1,313 @@
+use std::cell::{Ref, RefCell};
+use std::sync::Arc;
+use std::time::Duration;
+use log::*;
+use nannou::{rand::{rngs::StdRng, Rng, SeedableRng}, prelude::*};
+
+use crate::color::{rotate_hue, Rgba};
+use crate::dot::{ArenaShape, BubbleMesh, Dot, Nannou, Phase, Point, fallback_bounds};
+use crate::grid::SpatialGrid;
+use crate::particle::{Particle, MAX_PARTICLES};
+use crate::settings::Settings;
+use crate::sim;
+use crate::stats::Tally;
+
+/// An independent population of dots with its own settings.
+///
+/// Layers are drawn in order, so later layers appear on top.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub settings: Settings,
+    pub dots: Vec<Dot>,
+    pub particles: Vec<Particle>,
+    /// Effective population cap, refreshed from the settings and window size
+    pub cap: usize,
+    /// Cap easing towards its target when smoothed, floored into `cap`
+    pub smoothed_cap: f32,
+    /// Area new dots spawn in, following the window size but not the view
+    pub bounds: Rect,
+    /// Cursor in world coordinates while it pulls on the dots, set each frame
+    pub attractor: Option<Point>,
+    /// Source of every random choice in this layer, so seeded runs repeat exactly
+    pub rng: StdRng,
+    /// Simulated time, which stops while paused
+    pub clock: Duration,
+    /// Spawns and deaths since the layer was created
+    pub tally: Tally,
+    /// Colors loaded with `--palette`, shared by every layer. Empty for random colors.
+    pub palette: Arc<[Rgba]>,
+    /// Invalid distribution parameters already warned about
+    pub fallbacks: sim::Fallbacks,
+    /// Serial for the next dot spawned
+    pub next_serial: u64,
+    /// Indices of `dots` in draw order, rebuilt in place by `draw_order`
+    pub order: RefCell<Vec<usize>>,
+    /// Dots removed by the last update, kept to reuse the allocation
+    pub deaths: Vec<sim::Death>,
+}
+
+impl Nannou for Layer {
+    fn display(&self, draw: &Draw) {
+        if self.settings.batch_draw {
+            // Pop rings are strokes, which the mesh doesn't batch
+            let mut mesh = BubbleMesh::default();
+            let (popping, growing): (Vec<_>, Vec<_>) = self.draw_order().iter()
+                .map(|&i| &self.dots[i])
+                .partition(|d| d.phase != Phase::Growing);
+            growing.into_iter().for_each(|d| mesh.push(d));
+            mesh.display(draw);
+            popping.into_iter().for_each(|d| d.display(draw));
+            self.dots.iter().filter(|d| d.frozen).for_each(|d| d.draw_highlight(draw));
+            self.particles.iter().for_each(|p| p.display(draw));
+        } else {
+            self.entities().for_each(|e| e.display(draw));
+        }
+
+        if self.settings.arena == ArenaShape::Circle {
+            draw.ellipse()
+                .no_fill()
+                .stroke_weight(2.0)
+                .stroke_color(SILVER)
+                .radius(self.settings.arena_radius);
+        }
+    }
+
+    /// Spawns within the bounds as of the last `update_with_bounds`
+    fn update_with_bounds(&mut self, update: &Update, bounds: Rect) {
+        self.bounds = bounds;
+        self.update(update);
+    }
+
+    fn update(&mut self, update: &Update) {
+        self.clock += update.since_last;
+        let settings = &self.settings;
+        sim::step(&mut self.dots, settings, self.bounds, self.attractor, update, &mut self.deaths);
+        self.tally.record(&self.deaths);
+
+        self.particles.iter_mut().for_each(|p| p.update(update));
+        self.particles.retain(|p| p.alive());
+
+        if settings.pop_particles {
+            for death in self.deaths.iter().filter(|d| d.cause.natural()) {
+                let room = MAX_PARTICLES.saturating_sub(self.particles.len());
+                let count = room.min(settings.particle_count.into());
+                self.particles.extend(Particle::burst(&mut self.rng, death.origin, death.color, count));
+            }
+        }
+
+        // Reserve the whole cap at once when it grows rather than reallocating as dots trickle in.
+        // Culling happens in place, so capacity is kept between frames.
+        self.dots.reserve(self.cap.saturating_sub(self.dots.len()));
+
+        let room = if self.throttled() { 0 } else { self.cap.saturating_sub(self.dots.len()) };
+        for _ in 0..room.min(self.settings.spawn_per_frame.into()) {
+            if !self.spawn_one() {
+                break
+            }
+        }
+    }
+}
+
+impl Layer {
+    /// Everything drawn in this layer, bubbles below their particles
+    pub fn entities(&self) -> impl Iterator<Item = &dyn Nannou> {
+        let order = self.draw_order();
+        let dots = (0..order.len()).map(move |k| &self.dots[order[k]] as &dyn Nannou);
+        let particles = self.particles.iter().map(|p| p as &dyn Nannou);
+        dots.chain(particles)
+    }
+
+    /// Move the cap towards `target`, at once or over about `cap_smoothing`
+    /// seconds of `delta` when smoothing
+    pub fn approach_cap(&mut self, target: usize, delta: f32) {
+        let target = target as f32;
+        let smoothing = self.settings.cap_smoothing;
+        self.smoothed_cap = if smoothing > 0.0 {
+            // Exponential approach, covering about 63% of the gap per time constant
+            self.smoothed_cap + (target - self.smoothed_cap) * (1.0 - (-delta / smoothing).exp())
+        } else {
+            target
+        };
+        // Round the last fraction up so the cap ends on the target instead of one short
+        self.cap = if (target - self.smoothed_cap).abs() < 0.5 { target } else { self.smoothed_cap.floor() } as usize;
+    }
+
+    /// Indices of the dots in the order they are drawn.
+    ///
+    /// Spawn order unless depth sorting, which draws the largest first, without
+    /// reordering `dots` itself. The indices are sorted into a buffer kept
+    /// between frames, so this doesn't allocate once the buffer has grown.
+    pub fn draw_order(&self) -> Ref<'_, Vec<usize>> {
+        {
+            let mut order = self.order.borrow_mut();
+            order.clear();
+            order.extend(0..self.dots.len());
+            // Unstable sorts don't allocate. Ties fall back to the index to stay deterministic.
+            let spawned = |i: usize| (self.dots[i].serial, i);
+            if self.settings.depth_sort {
+                order.sort_unstable_by(|&a, &b| {
+                    self.dots[b].radius.total_cmp(&self.dots[a].radius).then(spawned(a).cmp(&spawned(b)))
+                });
+            } else {
+                order.sort_unstable_by_key(|&i| spawned(i));
+            }
+        }
+        self.order.borrow()
+    }
+
+    pub fn new(settings: Settings, rng: StdRng) -> Self {
+        Layer {
+            settings,
+            dots: Vec::with_capacity(settings.max_count.into()),
+            particles: Vec::new(),
+            cap: settings.max_count.into(),
+            smoothed_cap: settings.max_count.into(),
+            bounds: fallback_bounds(),
+            attractor: None,
+            rng,
+            clock: Duration::ZERO,
+            tally: Tally::default(),
+            palette: Arc::from([]),
+            fallbacks: sim::Fallbacks::default(),
+            next_serial: 0,
+            order: RefCell::new(Vec::with_capacity(settings.max_count.into())),
+            deaths: Vec::new(),
+        }
+    }
+
+    /// New empty layer with the same settings, seeded from this layer's RNG
+    pub fn fork(&mut self) -> Self {
+        let rng = StdRng::seed_from_u64(self.rng.gen());
+        Layer {
+            palette: self.palette.clone(),
+            ..Layer::new(self.settings, rng)
+        }
+    }
+
+    /// Dots bucketed by position as they are now, indexed like `dots`
+    pub fn grid(&self) -> SpatialGrid {
+        SpatialGrid::new(self.dots.iter().map(|d| (d.origin, d.extent())))
+    }
+
+    /// Summed dot area as a fraction of the spawn bounds, which can exceed 1 with overlap
+    pub fn coverage(&self) -> f32 {
+        let area = self.bounds.w() * self.bounds.h();
+        if area <= 0.0 {
+            return 0.0
+        }
+        self.dots.iter().map(|d| PI * d.extent().powi(2)).sum::<f32>() / area
+    }
+
+    /// Spawning is held back until enough dots pop to uncover the window
+    pub fn throttled(&self) -> bool {
+        self.settings.throttle_coverage && self.coverage() >= self.settings.max_coverage
+    }
+
+    /// Push the current render settings onto every dot
+    pub fn restyle(&mut self) {
+        let mut style = self.settings.style();
+        if !self.settings.hue_new_only {
+            // Derived from the clock each frame so stored colors never drift
+            style.hue_shift = self.hue_shift();
+        }
+        self.dots.iter_mut().for_each(|d| d.style = style);
+    }
+
+    /// Spawn a single dot sampled from the current settings.
+    ///
+    /// With a minimum spacing, positions too close to existing dots are redrawn
+    /// up to `spacing_retries` times. Returns whether a dot was spawned.
+    pub fn spawn_one(&mut self) -> bool {
+        let region = self.settings.spawn_region;
+        let scale = self.settings.spawn_scale;
+        let stretch = vec2(self.settings.spawn_stretch_x, self.settings.spawn_stretch_y);
+        let spacing = self.settings.min_spacing;
+
+        // Inflated by the spacing so a point query finds every dot the gap could reach
+        let grid = (spacing > 0.0)
+            .then(|| SpatialGrid::new(self.dots.iter().map(|d| (d.origin, d.extent() + spacing))));
+        let crowded = |p: Point| grid.as_ref().is_some_and(|grid| {
+            grid.query_point(p).any(|i| self.dots[i].origin.distance(p) < self.dots[i].extent() + spacing)
+        });
+
+        let tries = if grid.is_some() { self.settings.spacing_retries.max(1) } else { 1 };
+        for _ in 0..tries {
+            let origin = region.sample(&mut self.rng, self.bounds, scale, stretch);
+            let pivot = region.sample(&mut self.rng, self.bounds, scale, stretch);
+            if !crowded(origin) {
+                return self.spawn_at(origin, pivot)
+            }
+        }
+        false
+    }
+
+    /// Spawn a dot at a chosen position, sampling everything else from the settings.
+    ///
+    /// Returns false, leaving the layer as it was, if the settings produce an invalid dot.
+    pub fn spawn_at(&mut self, origin: Point, pivot: Point) -> bool {
+        let mut dot = sim::sample_dot(&self.settings, &self.palette, &mut self.fallbacks, &mut self.rng, origin, pivot);
+        if let Err(e) = dot.validate() {
+            warn!("Skipping invalid dot: {}", e);
+            return false
+        }
+        if self.settings.hue_new_only {
+            dot.color = rotate_hue(dot.color, self.hue_shift());
+        }
+        self.tally.spawned += 1;
+        self.tally.max_radius_total += dot.max_radius as f64;
+        dot.serial = self.next_serial;
+        self.next_serial += 1;
+        self.dots.push(dot);
+        true
+    }
+
+    /// Hue rotation reached so far, in degrees
+    fn hue_shift(&self) -> f32 {
+        (self.settings.hue_rate * self.clock.as_secs_f32()).rem_euclid(360.0)
+    }
+
+    /// Spawn dots until the population reaches its cap
+    pub fn fill(&mut self) {
+        while self.dots.len() < self.cap {
+            if !self.spawn_one() {
+                break
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawned_colors(seed: u64) -> Vec<Rgba> {
+        let mut layer = Layer::new(Settings { max_count: 200, ..Settings::default() }, StdRng::seed_from_u64(seed));
+        layer.fill();
+        layer.dots.iter().map(|d| d.color).collect()
+    }
+
+    #[test]
+    fn identically_seeded_layers_spawn_the_same_colors() {
+        let colors = spawned_colors(9);
+        assert_eq!(colors.len(), 200);
+        assert_eq!(colors, spawned_colors(9));
+        assert_ne!(colors, spawned_colors(10));
+    }
+
+    #[test]
+    fn draw_order_follows_spawns_after_culling() {
+        let mut layer = Layer::new(Settings { max_count: 50, ..Settings::default() }, StdRng::seed_from_u64(2));
+        layer.fill();
+        for d in layer.dots.iter_mut().step_by(3) {
+            d.ttl = Duration::ZERO;
+        }
+        layer.cap = 0;
+        layer.update(&Update { since_last: Duration::from_millis(16), since_start: Duration::from_millis(16) });
+
+        assert_eq!(layer.dots.len(), 33);
+        let serials = layer.draw_order().iter().map(|&i| layer.dots[i].serial).collect::<Vec<_>>();
+        assert!(serials.windows(2).all(|w| w[0] < w[1]));
+        // Swap-remove moved later dots into the gaps
+        assert!(layer.dots.windows(2).any(|w| w[0].serial > w[1].serial));
+    }
+}
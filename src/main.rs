@@ -1,424 +1,403 @@
-use std::time::Duration;
-use std::collections::VecDeque;
-use nannou::{color, rand, prelude::*};
-use palette::named;
-use structopt::StructOpt;
-use lazy_static::lazy_static;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use nannou::{rand::{rngs::StdRng, Rng, SeedableRng}, prelude::*};
+use nannou::winit::event::VirtualKeyCode;
 use log::*;
-use typed_builder::TypedBuilder;
-use rand_distr::{Distribution, Gamma};
-use histo::Histogram;
-
-use nannou_egui::{self, egui, Egui};
-use egui_plot::{Plot, Bar, BarChart};
-
-#[derive(Debug, StructOpt)]
-pub struct Opts {
-    /// Maximum angular velocity in radians per second
-    #[structopt(short, long, default_value="1.0")]
-    speed: f32,
-
-    /// Maximum bubble growth rate in pixels per second
-    #[structopt(short, long, default_value="100.0")]
-    rate: f32,
-
-    /// Maximum bubbles to render simultaneously
-    #[structopt(short, long, default_value="1")]
-    num_dots: u8,
-}
-
-lazy_static! {
-    pub static ref OPTS: Opts = Opts::from_args();
-}
-
-type Rgb = Srgb<u8>;
-type Rgba = Srgba<u8>;
-
-trait Nannou {
-    fn display(&self, draw: &Draw);
-    fn update(&mut self, update: &Update);
-}
 
-fn as_nn(c: palette::rgb::Rgb<palette::encoding::Srgb, u8>) -> Rgb {
-    rgb(c.red, c.green, c.blue)
-}
-
-fn from_str(name: &str) -> Option<Rgb> {
-    named::from_str(name).map(|v| as_nn(v))
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum Color {
-    Black,
-    DarkGray,
-    DimGray,
-    Honeydew,
-    SteelBlue,
-    SlateGray,
-    Silver,
-}
-
-impl ToString for Color {
-    fn to_string(&self) -> String {
-        format!("{:?}", self).to_lowercase()
-    }
+use nannou_egui::Egui;
+
+mod bench;
+mod color;
+mod dot;
+mod grid;
+mod headless;
+mod history;
+mod keys;
+mod layer;
+mod model;
+mod particle;
+mod quality;
+mod recording;
+mod replay;
+mod settings;
+mod sim;
+mod snapshot;
+mod stats;
+mod trail;
+mod ui;
+mod view;
+
+use color::Rgba;
+use dot::Nannou;
+use history::History;
+use keys::Command;
+use layer::Layer;
+use model::Model;
+use view::View;
+use quality::AutoQuality;
+use recording::Recording;
+use replay::{Event, EventLog, Replay};
+use settings::{Config, Settings, OPTS};
+use snapshot::Snapshot;
+use stats::StatsLog;
+
+/// Set by the Ctrl-C handler and polled on the main thread, where the model lives
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Settings from `--config` if it exists, otherwise the defaults
+fn load_config() -> Config {
+    let config = match &OPTS.config {
+        Some(path) if path.exists() => Config::load_from(path).unwrap_or_else(|e| {
+            warn!("{:#}, using defaults", e);
+            Config::default()
+        }),
+        _ => Config::default(),
+    };
+    config
 }
 
-impl From<Color> for Rgb {
-    fn from(c: Color) -> Self {
-        from_str(&c.to_string()).expect("Unknown color {}")
+/// Colors from `--palette`, or none for random coloring if absent or unreadable
+fn load_palette() -> Arc<[Rgba]> {
+    let colors = match &OPTS.palette {
+        Some(path) => color::load_palette(path).unwrap_or_else(|e| {
+            warn!("{:#}, using random colors", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    if let Some(path) = &OPTS.palette {
+        info!("Loaded {} colors from {}", colors.len(), path.display());
     }
+    colors.into()
 }
 
-type Point = Vec2;
-
-fn rand_point() -> Point {
-    Point::new(rand::random_range(-500.0, 500.0), rand::random_range(-500.0, 500.0))
-}
-
-#[derive(Debug, Clone, Copy, TypedBuilder)]
-struct Dot {
-    #[builder(setter(into))]
-    color: Rgba,
-    #[builder(setter(into), default)]
-    origin: Point,
-    #[builder(setter(into), default)]
-    pivot: Point,
-    #[builder(default=10.0)]
-    radius: f32,
-    #[builder(default=200.0)]
-    max_radius: f32,
-    #[builder(default=OPTS.rate)]
-    speed: f32,
-    #[builder(default=OPTS.rate)]
-    growth_rate: f32,
-    #[builder(default=Duration::from_secs(0))]
-    age: Duration,
-    #[builder(default=Duration::from_secs(10))]
-    ttl: Duration,
+/// Layers for each settings entry, all seeded from `seed`
+fn seeded_layers(settings: Vec<Settings>, seed: u64) -> Vec<Layer> {
+    // Each layer gets its own stream so adding layers doesn't perturb the others
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let palette = load_palette();
+    settings.into_iter()
+        .map(|settings| Layer {
+            palette: palette.clone(),
+            ..Layer::new(settings, StdRng::seed_from_u64(rng.gen()))
+        })
+        .collect()
 }
 
-impl Nannou for Dot {
-    fn display(&self, draw: &Draw) {
-        draw.ellipse()
-            .color(self.color)
-            .w(self.radius)
-            .h(self.radius)
-            .x_y(self.origin.x, self.origin.y);
+fn model(app: &App) -> Model {
+    let mut window = app.new_window()
+        .view(view)
+        .raw_event(raw_window_event);
+    if OPTS.width.is_some() || OPTS.height.is_some() {
+        let default = nannou::window::DEFAULT_DIMENSIONS;
+        window = window.size(OPTS.width.unwrap_or(default.width as u32),
+                             OPTS.height.unwrap_or(default.height as u32));
     }
+    let title = base_title();
+    window = window.title(&title);
+    let wid = window.build().unwrap();
+    let window = app.window(wid).unwrap();
+    let egui = Egui::from_window(&window);
 
-    fn update(&mut self, update: &Update) {
-        let delta = update.since_last;
-        self.ttl = self.ttl.checked_sub(delta).unwrap_or(Duration::ZERO);
-        self.age += delta;
+    let config = load_config();
+
+    let recording = OPTS.record.and_then(|secs| {
+        Recording::new(Duration::from_secs_f32(secs.max(0.0)), OPTS.record_every)
+            .map_err(|e| warn!("{:#}, not recording", e))
+            .ok()
+    });
+
+    let replay = OPTS.replay.as_ref().and_then(|path| {
+        Replay::load(path)
+            .map_err(|e| warn!("{:#}, running live", e))
+            .ok()
+    });
+    // A replay only reproduces its session from the seed it was recorded with
+    let seed = replay.as_ref().and_then(Replay::seed)
+        .or(OPTS.seed)
+        .unwrap_or_else(random);
+
+    let event_log = OPTS.log_events.as_ref().and_then(|path| {
+        let mut log = EventLog::create(path)
+            .map_err(|e| warn!("{:#}, not logging events", e))
+            .ok()?;
+        log.write(Duration::ZERO, Event::Seed(seed));
+        Some(log)
+    });
 
-        let delta = delta.as_secs_f32();
-        if self.radius < self.max_radius {
-            self.radius += self.growth_rate * delta;
-        }
+    let mut ring_buf = VecDeque::new();
+    ring_buf.resize(360, 0.0);
 
-        let offset = self.origin - self.pivot;
-        let step = self.speed * delta;
-        self.origin = self.pivot + offset.rotate(step);
+    let mut model = Model {
+        egui,
+        scene: config.scene,
+        layers: seeded_layers(config.layers.clone(), seed),
+        history: History::default(),
+        quality: AutoQuality::default(),
+        active: 0,
+        ring_buf,
+        frame_times: VecDeque::new(),
+        population: VecDeque::new(),
+        view: View::default(),
+        pan_from: None,
+        cursor: Point2::ZERO,
+        emitting: false,
+        emit_carry: 0.0,
+        auto_paused: false,
+        held_keys: HashSet::new(),
+        stats_log: StatsLog::new(OPTS.stats_interval),
+        peak: 0,
+        sim_time: Duration::ZERO,
+        window_rect: window.rect(),
+        screenshot_requested: false,
+        pending_screenshots: Vec::new(),
+        hide_ui: false,
+        show_help: false,
+        step_requested: false,
+        base_title: title.clone(),
+        shown_title: title,
+        title_age: Duration::ZERO,
+        recording,
+        defaults: config,
+        shut_down: false,
+        trail: RefCell::new(None),
+        event_log,
+        replay,
+    };
+    if let Some(snapshot) = load_snapshot() {
+        model.restore(snapshot);
     }
+    // Settings are logged per layer, so a different count would apply them to the wrong layers
+    let recorded = model.replay.as_ref().and_then(Replay::layers);
+    if let Some(recorded) = recorded.filter(|&n| n != model.layers.len()) {
+        warn!("Replay was recorded with {} layers but {} are loaded, running live", recorded, model.layers.len());
+        model.replay = None;
+    }
+    model
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Settings {
-    paused: bool,
-    bg_color: Rgb,
-    max_count: u8,
-    max_speed: f32,
-    max_rate: f32,
-    scale: f32,
-    shape: f32,
-}
-
-struct Model {
-    egui: Egui,
-    settings: Settings,
-    dots: Vec<Dot>,
-    x_limit: u64,
-    ring_buf: VecDeque<f32>,
+/// `--title`, or the name nannou gives windows by default
+fn base_title() -> String {
+    OPTS.title.clone().unwrap_or_else(|| {
+        let exe = std::env::current_exe().ok()
+            .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()));
+        format!("nannou - {}", exe.unwrap_or_default())
+    })
 }
 
-pub trait ColorExt<C> {
-    fn with_alpha<A>(&self, alpha: A) -> color::Alpha<C,A>;
+/// Scene from `--scene`, if given and readable
+fn load_snapshot() -> Option<Snapshot> {
+    let path = OPTS.scene.as_ref().filter(|p| p.exists())?;
+    Snapshot::load_from(path)
+        .map_err(|e| warn!("{:#}, starting afresh", e))
+        .ok()
 }
 
-impl ColorExt<Rgb> for Rgb {
-    fn with_alpha<A>(&self, alpha: A) -> color::Alpha<Rgb,A> {
-        color::Alpha {
-            color: *self,
-            alpha,
+fn update(app: &App, model: &mut Model, update: Update) {
+    // Screenshots are written after the frame they were requested for renders
+    if !model.pending_screenshots.is_empty() {
+        await_captures(app);
+        for path in model.pending_screenshots.drain(..) {
+            if let Err(e) = recording::linearize_file(&path) {
+                warn!("{:#}", e);
+            }
         }
     }
-}
 
-impl Nannou for Model {
-    fn display(&self, draw: &Draw) {
-        draw.background()
-            .color(self.settings.bg_color);
-
-        self.dots.iter().for_each(|d| d.display(draw));
-
-        const RADIUS: f32 = 100.0;
-
-        let rads = (0..=360).map(|d| (d as f32).to_radians());
-        let in_points = rads.clone().map(|r| vec2(0.0, RADIUS).rotate(r)).collect::<Vec<_>>();
-        let out_points = rads.enumerate().map(|(d, r)| {
-            vec2(0.0, RADIUS + 100.0 * self.ring_buf[d % 360]).rotate(-r)
-        }).collect::<Vec<_>>();
-
-        let all_points = [in_points, out_points.clone()].concat();
-        draw.polygon()
-            .color(WHITE.with_alpha(0.2))
-            //.stroke_weight(2.0)
-            //.stroke_color(BLACK)
-            .points(all_points);
-
-        // Only outside stroke
-        draw.polyline()
-            .weight(3.0)
-            .color(STEELBLUE.with_alpha(0.8))
-            .points_closed(out_points.clone());
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        await_captures(app);
+        model.shutdown();
+        app.quit();
+        return
     }
 
-    fn update(&mut self, update: &Update) {
-        let egui = &mut self.egui;
-        let settings = &mut self.settings;
-
-        egui.set_elapsed_time(update.since_start);
-
-        let ctx = egui.begin_frame();
-        let mut dump = false;
-
-        egui::Window::new("Settings")
-            .anchor(egui::Align2::LEFT_TOP, (0.0, 0.0))
-            .show(&ctx, |ui| {
-                ui.horizontal_wrapped(|ui| {
-                    dump = ui.button("Dump").clicked();
-
-                    if ui.button("Clear").clicked() {
-                        self.dots.clear();
-                    }
-
-                    let paused = settings.paused;
-                    ui.toggle_value(&mut settings.paused, if paused {"Resume" } else {"Pause"});
-                });
-
-                ui.label("Max Dots:");
-                ui.add(egui::Slider::new(&mut settings.max_count, 1..=255));
-
-                ui.label("Max Speed:");
-                ui.add(egui::Slider::new(&mut settings.max_speed, 0.0..=10.0));
-
-                ui.label("Growth Rate:");
-                ui.add(egui::Slider::new(&mut settings.max_rate, 0.0..=1000.0));
-
-                ui.add_space(16.0);
-                ui.heading("Radius Distribution");
-
-                ui.label("Shape");
-                ui.add(egui::Slider::new(&mut settings.shape, 1.0..=500.0)
-                       .logarithmic(true));
-
-                ui.label("Scale");
-                ui.add(egui::Slider::new(&mut settings.scale, 1.0..=500.0)
-                       .logarithmic(true));
-            });
-
-
-        egui::Window::new("Speed")
-            .anchor(egui::Align2::RIGHT_TOP, (0.0, 0.0))
-            .show(&ctx, |ui| {
-                Plot::new("Dist")
-                    .view_aspect(1.5)
-                    .include_x(0.0)
-                    .include_x(settings.max_speed)
-                    .include_y(20.0)
-                    .y_axis_width(2)
-                    .show(ui, |plt| {
-                        const SCALE: f64 = 1000.0;
-                        let mut hist = Histogram::with_buckets(10);
-                        for d in self.dots.iter() {
-                            let speed = d.speed.abs() as f64 * SCALE;
-                            hist.add(speed as u64);
-                        }
-
-                        let bars = hist.buckets().map(|b| {
-                            let center = (b.start() + b.end()) / 2;
-                            let width = b.end() - b.start();
-
-                            let center = center as f64 / SCALE;
-                            let width = width as f64 / SCALE;
-
-
-                            Bar::new(center as f64, b.count() as f64)
-                                .width(0.5 * width as f64)
-                        }).collect::<Vec<_>>();
-
-                        let chart1 = BarChart::new(bars)
-                            .name("Current");
-                        plt.bar_chart(chart1);
-                    });
-            });
-
-        let x_limit = self.x_limit as f64 * 0.995;
-        egui::Window::new("Radius")
-            .anchor(egui::Align2::RIGHT_BOTTOM, (0.0, 0.0))
-            .show(&ctx, |ui| {
-                Plot::new("Dist")
-                    .legend(Default::default())
-                    .view_aspect(1.5)
-                    .include_x(0.0)
-                    .include_x(x_limit)
-                    .include_y(50.0)
-                    .y_axis_width(2)
-                    .show(ui, |plt| {
-                        let mut hist = Histogram::with_buckets(10);
-                        for d in self.dots.iter() {
-                            hist.add(d.radius as u64);
-                        }
-
-                        let x_max1 = hist.buckets().map(|b| b.end()).max().unwrap_or(0);
-
-
-                        let bars = hist.buckets().map(|b| {
-                            let center = (b.start() + b.end()) / 2;
-                            let width = b.end() - b.start();
-
-                            Bar::new(center as f64, b.count() as f64)
-                                .width(0.5 * width as f64)
-                        }).collect::<Vec<_>>();
-
-                        let chart1 = BarChart::new(bars)
-                            .name("Current");
-                        plt.bar_chart(chart1);
-
-                        let mut hist = Histogram::with_buckets(10);
-                        for d in self.dots.iter() {
-                            hist.add(d.max_radius as u64);
-                        }
-
-                        let x_max2 = hist.buckets().map(|b| b.end()).max().unwrap_or(0);
-
-                        let bars = hist.buckets().map(|b| {
-                            let center = (b.start() + b.end()) / 2;
-                            let width = b.end() - b.start();
-                            Bar::new(center as f64, b.count() as f64)
-                                .width(0.5 * width as f64)
-                        }).collect::<Vec<_>>();
-
-                        let chart1 = BarChart::new(bars)
-                            .name("Maximum");
-                        plt.bar_chart(chart1);
-
-                        self.x_limit = vec![x_limit as u64, x_max1, x_max2].into_iter().max().unwrap_or(100);
-                    });
-            });
-
-        if dump {
-            // Some debugging data
-            dbg!(&settings, &self.dots);
-        }
+    // Follows the Limit FPS controls as they change
+    let loop_mode = model.loop_mode();
+    if app.loop_mode() != loop_mode {
+        app.set_loop_mode(loop_mode);
+    }
+    model.window_rect = app.window_rect();
+    model.cursor = app.mouse.position();
+    if let Some(from) = model.pan_from {
+        model.view.pan(model.cursor - from);
+        model.pan_from = Some(model.cursor);
+    }
 
-        if settings.paused {
-            return
+    // The UI is hidden for the single frame following a capture request
+    model.hide_ui = false;
+    if model.screenshot_requested {
+        model.screenshot_requested = false;
+        model.hide_ui = OPTS.hide_ui_on_capture;
+        let path = save_screenshot(app);
+        if OPTS.linear_capture {
+            model.pending_screenshots.push(path);
         }
+    }
 
-        let avg_age = (!self.dots.is_empty())
-            .then(|| self.dots.iter().map(|d| d.age.as_secs_f32()).sum())
-            .map(|s: f32| s / 10.0 / self.dots.len() as f32)
-            .unwrap_or(0.0);
-
-        self.ring_buf.pop_back();
-        self.ring_buf.push_front(avg_age);
-
-        self.dots.iter_mut().for_each(|d| d.update(update));
-        self.dots.retain(|d| d.ttl > Duration::ZERO && d.radius < d.max_radius);
-
-        let radius_dist = Gamma::new(settings.shape, settings.scale).unwrap();
-        let max_radius: f32 = radius_dist.sample(&mut rand::thread_rng());
-        let max_radius = max_radius.clamp(0.0, 512.0);
-
-        if self.dots.len() < settings.max_count.into() {
-            self.dots.push(
-                Dot::builder()
-                .color(random_color())
-                .origin(rand_point())
-                .pivot(rand_point())
-                .max_radius(max_radius)
-                .speed(rand::random_range(-settings.max_speed, settings.max_speed))
-                .growth_rate(rand::random_range(1.0, settings.max_rate))
-                .ttl(Duration::from_secs_f32(rand::random_range(1.0, 10.0)))
-                .build());
-        }
+    model.update(&update);
+    if let Some(title) = model.status_title(update.since_last) {
+        app.main_window().set_title(&title);
     }
-}
 
-fn random_color() -> Rgba {
-    rgba(
-        rand::random_range(0, 128),
-        rand::random_range(0, 255),
-        rand::random_range(0, 255),
-        rand::random_range(128, 255),
-    )
+    if let Some(recording) = &mut model.recording {
+        recording.tick(&app.main_window(), update.since_last);
+        if recording.done() {
+            await_captures(app);
+            model.finish_recording();
+        }
+    }
 }
 
-fn model(app: &App) -> Model {
-    let wid = app.new_window()
-        .view(view)
-        .raw_event(raw_window_event)
-        .build()
-        .unwrap();
-    let window = app.window(wid).unwrap();
-    let egui = Egui::from_window(&window);
-
-    let settings = Settings {
-        bg_color: Color::DimGray.into(),
-        paused: false,
-        max_count: OPTS.num_dots.into(),
-        max_speed: OPTS.speed,
-        max_rate: OPTS.rate,
-        scale: 10.0,
-        shape: 10.0,
-    };
-
-    let mut ring_buf = VecDeque::new();
-    ring_buf.resize(360, 0.0);
-
-    Model {
-        egui,
-        settings,
-        dots: Vec::new(),
-        x_limit: 100,
-        ring_buf,
+/// Block until every pending frame capture has been written to disk
+fn await_captures(app: &App) {
+    for window in app.window_ids().into_iter().filter_map(|id| app.window(id)) {
+        if window.await_capture_frame_jobs().is_err() {
+            warn!("Timed out waiting for frame captures");
+        }
     }
 }
 
-fn update(_app: &App, model: &mut Model, update: Update) {
-    model.update(&update);
+/// Capture the next rendered frame to a PNG named after the time and frame number
+fn save_screenshot(app: &App) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = PathBuf::from(format!("bubbles-{}-{:06}.png", secs, app.elapsed_frames()));
+    info!("Saving screenshot to {}", path.display());
+    app.main_window().capture_frame(&path);
+    path
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
 
     model.display(&draw);
-    draw.to_frame(app, &frame).unwrap();
-    model.egui.draw_to_frame(&frame).unwrap();
+
+    // Compare with RUST_LOG=trace to see the cost of batched vs per-ellipse drawing
+    let start = Instant::now();
+    if model.trails() {
+        let scale_factor = app.window(frame.window_id()).map_or(1.0, |w| w.scale_factor());
+        model.render_trails(&draw, scale_factor, &frame);
+    } else {
+        draw.to_frame(app, &frame).unwrap();
+    }
+    trace!("Submitted {} dots in {:?}", model.dots().count(), start.elapsed());
+    if !model.hide_ui {
+        model.egui.draw_to_frame(&frame).unwrap();
+    }
 }
 
-fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+fn raw_window_event(app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+    use nannou::winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, WindowEvent};
+
     // Let egui handle things like keyboard and mouse input.
     model.egui.handle_raw_event(event);
+
+    match event {
+        WindowEvent::Resized(size) => {
+            let size = size.to_logical::<f32>(app.main_window().scale_factor() as f64);
+            model.resize(Rect::from_w_h(size.width, size.height));
+        }
+        WindowEvent::Focused(focused) => model.focus(*focused),
+        WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+            // Clicks on the settings windows belong to egui
+            let ctx = model.egui.ctx();
+            if !ctx.is_pointer_over_area() && !ctx.wants_pointer_input() {
+                // Already converted from physical pixels to centered logical coordinates
+                model.click(app.mouse.position());
+            }
+        }
+        WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+            model.emitting = false;
+        }
+        WindowEvent::MouseInput { state, button: MouseButton::Middle, .. } => {
+            let ctx = model.egui.ctx();
+            model.pan_from = match state {
+                ElementState::Pressed if !ctx.is_pointer_over_area() => Some(app.mouse.position()),
+                _ => None,
+            };
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let ctx = model.egui.ctx();
+            if !ctx.is_pointer_over_area() && !ctx.wants_pointer_input() {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    // Roughly one line per 20 pixels of touchpad scrolling
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+                };
+                model.view.zoom_at(app.mouse.position(), lines);
+            }
+        }
+        WindowEvent::KeyboardInput {
+            input: KeyboardInput { state, virtual_keycode: Some(key), .. },
+            ..
+        } => match state {
+            // Auto-repeat sends more presses while a key is held, so act only on the first
+            ElementState::Pressed => {
+                if model.held_keys.insert(*key) && !model.egui.ctx().wants_keyboard_input() {
+                    key_pressed(model, *key);
+                }
+            }
+            ElementState::Released => {
+                model.held_keys.remove(key);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Keyboard shortcuts from `keys::BINDINGS`, mirroring buttons in the settings window
+fn key_pressed(model: &mut Model, key: VirtualKeyCode) {
+    match keys::command(key) {
+        Some(Command::TogglePause) => model.scene.paused = !model.scene.paused,
+        Some(Command::Burst) => model.burst(),
+        Some(Command::Clear) => model.clear(false),
+        Some(Command::Dump) => model.dump(),
+        Some(Command::Screenshot) => model.screenshot_requested = true,
+        Some(Command::ToggleHelp) => model.show_help = !model.show_help,
+        Some(Command::Step) => model.step_requested = true,
+        None => {}
+    }
+}
+
+fn exit(app: &App, mut model: Model) {
+    await_captures(app);
+    model.shutdown();
 }
 
 fn main() {
     pretty_env_logger::init();
     info!("Options: {:?}", *OPTS);
+
+    if let Err(e) = OPTS.validate() {
+        eprintln!("error: {:#}", e);
+        std::process::exit(2);
+    }
+
+    if let Some(frames) = OPTS.headless {
+        let seed = OPTS.seed.unwrap_or_else(random);
+        headless::run(&mut seeded_layers(load_config().layers, seed), frames);
+        return
+    }
+
+    if let Some(frames) = OPTS.bench_draw {
+        let seed = OPTS.seed.unwrap_or_else(random);
+        bench::run(&mut seeded_layers(load_config().layers, seed)[0], frames);
+        return
+    }
+
+    if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        warn!("Unable to install Ctrl-C handler: {}", e);
+    }
+
     nannou::app(model)
         .update(update)
+        .exit(exit)
         .run();
 }
-
@@ -0,0 +1,940 @@
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use nannou::prelude::*;
+use nannou::winit::event::VirtualKeyCode;
+use nannou_egui::{egui, Egui};
+use log::*;
+
+use crate::color::{luminance, ColorExt, Rgb, Rgba};
+use crate::dot::{Dot, Nannou, Point};
+use crate::history::History;
+use crate::layer::Layer;
+use crate::quality::AutoQuality;
+use crate::recording::{self, Recording};
+use crate::replay::{Event, EventLog, Replay};
+use crate::settings::{Config, Scene, OPTS};
+use crate::snapshot::{self, LayerSnapshot, Snapshot};
+use crate::stats::{self, StatsLog, Tally};
+use crate::trail::Trail;
+use crate::ui;
+use crate::view::View;
+
+pub struct Model {
+    pub egui: Egui,
+    pub scene: Scene,
+    /// Bubble populations, drawn back to front
+    pub layers: Vec<Layer>,
+    pub history: History,
+    pub quality: AutoQuality,
+    /// Index of the layer being edited in the UI
+    pub active: usize,
+    pub ring_buf: VecDeque<f32>,
+    /// Recent frame times in seconds, newest last
+    pub frame_times: VecDeque<f32>,
+    /// Seconds since start and total dot count, newest last
+    pub population: VecDeque<(f64, usize)>,
+    /// Pan and zoom of the bubble field
+    pub view: View,
+    /// Cursor position the current middle-drag pan last moved from
+    pub pan_from: Option<Point>,
+    /// Mouse position in window coordinates as of the last update
+    pub cursor: Point,
+    /// Left button held after a click that spawned, emitting dots from the cursor
+    pub emitting: bool,
+    /// Fractional dots owed to emission from previous frames
+    pub emit_carry: f32,
+    /// Paused by losing focus rather than by the user
+    pub auto_paused: bool,
+    /// Keys currently down, to ignore auto-repeat
+    pub held_keys: HashSet<VirtualKeyCode>,
+    /// Periodic population summary in the log
+    pub stats_log: StatsLog,
+    /// Most dots alive at once this session
+    pub peak: usize,
+    /// Simulated time so far, advancing at the scene's time scale
+    pub sim_time: Duration,
+    /// Window bounds as of the last update
+    pub window_rect: Rect,
+    /// Capture the next frame to a PNG
+    pub screenshot_requested: bool,
+    /// Screenshots captured but not yet converted for `--linear-capture`
+    pub pending_screenshots: Vec<PathBuf>,
+    /// Skip drawing egui this frame
+    pub hide_ui: bool,
+    /// Keyboard help window is open
+    pub show_help: bool,
+    /// Advance one frame on the next update despite the pause
+    pub step_requested: bool,
+    /// Window title without the status line
+    pub base_title: String,
+    /// Title as last set on the window
+    pub shown_title: String,
+    /// Time since the status line was last refreshed
+    pub title_age: Duration,
+    /// In-progress `--record` capture, dropped once encoded
+    pub recording: Option<Recording>,
+    /// Settings as loaded at startup, restored by Reset
+    pub defaults: Config,
+    /// Cleanup has already run
+    pub shut_down: bool,
+    /// Offscreen scene kept between frames while trails are on
+    pub trail: RefCell<Option<Trail>>,
+    /// `--log-events` output, recording what the simulation is fed
+    pub event_log: Option<EventLog>,
+    /// `--replay` input, fed to the simulation in place of live input until exhausted
+    pub replay: Option<Replay>,
+}
+
+/// Full-window quad shaded from `top` to `bottom`
+fn draw_gradient(draw: &Draw, rect: Rect, top: Rgb, bottom: Rgb, alpha: f32) {
+    let (top, bottom) = (top.with_alpha(alpha), bottom.with_alpha(alpha));
+    let points = [
+        (rect.top_left().extend(0.0), top),
+        (rect.top_right().extend(0.0), top),
+        (rect.bottom_right().extend(0.0), bottom),
+        (rect.bottom_left().extend(0.0), bottom),
+    ];
+    draw.mesh().indexed_colored(points, [0, 1, 2, 0, 2, 3]);
+}
+
+/// Grid lines closer together than this on screen are skipped, to avoid a solid wash
+const MIN_GRID_PIXELS: f32 = 4.0;
+
+/// Faint lines or text that stand out a little from `bg`, dark over light backgrounds
+fn overlay_color(bg: Rgb) -> Rgba {
+    let c = if luminance(bg) > 0.5 { BLACK } else { WHITE };
+    c.with_alpha(0.15).into_format()
+}
+
+/// Lines every `spacing` across the visible part of the world, one screen pixel wide
+fn draw_grid(draw: &Draw, view: &View, window: Rect, spacing: f32, color: Rgba) {
+    if spacing * view.zoom < MIN_GRID_PIXELS {
+        return
+    }
+
+    let world = view.apply(draw);
+    let area = view.visible(window);
+    let weight = 1.0 / view.zoom;
+    let lines = |lo: f32, hi: f32| {
+        ((lo / spacing).floor() as i64..=(hi / spacing).ceil() as i64).map(move |i| i as f32 * spacing)
+    };
+
+    for x in lines(area.left(), area.right()) {
+        world.line().start(pt2(x, area.bottom())).end(pt2(x, area.top())).weight(weight).color(color);
+    }
+    for y in lines(area.bottom(), area.top()) {
+        world.line().start(pt2(area.left(), y)).end(pt2(area.right(), y)).weight(weight).color(color);
+    }
+}
+
+/// Margin between the scale bar and the window corner
+const SCALE_BAR_MARGIN: f32 = 20.0;
+
+/// Bar in the bottom right corner as long on screen as `length` of world at the current zoom
+fn draw_scale_bar(draw: &Draw, view: &View, window: Rect, length: f32, color: Rgba) {
+    let color = rgba(color.red, color.green, color.blue, 200);
+    let end = window.bottom_right() + vec2(-SCALE_BAR_MARGIN, SCALE_BAR_MARGIN);
+    let start = end - vec2(length * view.zoom, 0.0);
+
+    draw.line().start(start).end(end).weight(3.0).color(color);
+    draw.text(&format!("{} px", length.round()))
+        .xy((start + end) / 2.0 + vec2(0.0, 12.0))
+        .color(color)
+        .font_size(12);
+}
+
+/// Layer and index of the topmost dot under `point`.
+///
+/// Later layers and later dots in draw order are drawn over earlier ones, so search backwards.
+fn hit(layers: &[Layer], point: Point) -> Option<(usize, usize)> {
+    layers.iter().enumerate().rev().find_map(|(l, layer)| {
+        let under = layer.grid().query_point(point)
+            .filter(|&i| layer.dots[i].origin.distance(point) <= layer.dots[i].extent())
+            .collect::<Vec<_>>();
+        if under.is_empty() {
+            return None
+        }
+        layer.draw_order().iter().rev()
+            .find(|i| under.contains(i))
+            .map(|&i| (l, i))
+    })
+}
+
+/// Scene file from `--scene`, or the default
+fn scene_path() -> PathBuf {
+    OPTS.scene.clone().unwrap_or_else(|| PathBuf::from(snapshot::DEFAULT_PATH))
+}
+
+/// Frames kept for the FPS overlay
+const FRAME_HISTORY: usize = 120;
+
+/// Samples kept for the population plot
+const POPULATION_HISTORY: usize = 600;
+
+/// Seconds of travel represented by the velocity arrows in the debug overlay
+const VECTOR_SCALE: f32 = 0.2;
+
+/// Least time between status line refreshes, to spare the window system
+const TITLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Velocity arrow, pivot link and optionally the orbit circle of a dot
+fn draw_debug_vectors(draw: &Draw, dot: &Dot, orbit: bool) {
+    draw.line()
+        .start(dot.origin)
+        .end(dot.pivot)
+        .weight(1.0)
+        .color(WHITE.with_alpha(0.2));
+
+    draw.arrow()
+        .start(dot.origin)
+        .end(dot.origin + dot.velocity() * VECTOR_SCALE)
+        .weight(2.0)
+        .color(YELLOW.with_alpha(0.8));
+
+    if orbit {
+        draw.ellipse()
+            .no_fill()
+            .stroke_weight(1.0)
+            .stroke_color(WHITE.with_alpha(0.1))
+            .radius(dot.origin.distance(dot.pivot))
+            .xy(dot.pivot);
+    }
+}
+
+impl Nannou for Model {
+    fn display(&self, draw: &Draw) {
+        // With trails the last frame is still there, so cover it partially instead of clearing
+        let alpha = 1.0 - self.scene.trail_persistence;
+        if self.trails() {
+            draw.rect()
+                .wh(self.window_rect.wh())
+                .color(self.scene.bg_color.with_alpha(alpha));
+        } else {
+            draw.background()
+                .color(self.scene.bg_color);
+        }
+
+        if self.scene.gradient && !self.quality.degraded() {
+            draw_gradient(draw, self.window_rect, self.scene.bg_top, self.scene.bg_bottom, alpha);
+        }
+
+        if self.scene.show_grid {
+            let color = overlay_color(self.scene.bg_color);
+            draw_grid(draw, &self.view, self.window_rect, self.scene.grid_spacing, color);
+        }
+
+        let world = self.view.apply(draw);
+        self.layers.iter().for_each(|l| l.display(&world));
+
+        if self.scene.show_scale_bar {
+            let color = overlay_color(self.scene.bg_color);
+            draw_scale_bar(draw, &self.view, self.window_rect, self.scene.grid_spacing, color);
+        }
+
+        if self.scene.debug_vectors {
+            self.dots().for_each(|d| draw_debug_vectors(&world, d, self.scene.debug_orbits));
+        }
+
+        const RADIUS: f32 = 100.0;
+
+        let rads = (0..=360).map(|d| (d as f32).to_radians());
+        let in_points = rads.clone().map(|r| vec2(0.0, RADIUS).rotate(r)).collect::<Vec<_>>();
+        let out_points = rads.enumerate().map(|(d, r)| {
+            vec2(0.0, RADIUS + 100.0 * self.ring_buf[d % 360]).rotate(-r)
+        }).collect::<Vec<_>>();
+
+        let all_points = [in_points, out_points.clone()].concat();
+        draw.polygon()
+            .color(WHITE.with_alpha(0.2))
+            //.stroke_weight(2.0)
+            //.stroke_color(BLACK)
+            .points(all_points);
+
+        // Only outside stroke
+        draw.polyline()
+            .weight(3.0)
+            .color(STEELBLUE.with_alpha(0.8))
+            .points_closed(out_points.clone());
+    }
+
+    fn update(&mut self, update: &Update) {
+        // Dropped when off so trails start afresh next time
+        if !self.trails() {
+            *self.trail.get_mut() = None;
+        }
+
+        let egui = &mut self.egui;
+
+        egui.set_elapsed_time(update.since_start);
+
+        if self.frame_times.len() >= FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(update.since_last.as_secs_f32());
+        self.stats_log.tick(update.since_last, &self.layers);
+
+        let ctx = egui.begin_frame();
+        self.scene.theme.apply(&ctx, &self.scene);
+
+        let mut actions = ui::Actions::default();
+        let side_panel = self.scene.side_panel;
+        let mut overlays = |parent: &mut ui::Parent| {
+            actions = ui::settings_window(parent, &mut self.scene, &mut self.layers, &mut self.active, &self.history, &self.quality);
+            let layer = &self.layers[self.active];
+            ui::speed_window(parent, &layer.settings, &layer.dots);
+            ui::radius_window(parent, &layer.settings, &layer.dots);
+            if self.scene.show_fps {
+                ui::fps_window(parent, &self.frame_times);
+            }
+            if self.scene.show_population {
+                ui::population_window(parent, &self.population);
+            }
+        };
+        if side_panel {
+            egui::SidePanel::left("Overlays").show(&ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| overlays(&mut ui::Parent::Panel(ui)));
+            });
+        } else {
+            overlays(&mut ui::Parent::Windows(&ctx));
+        }
+        if actions.toggle_help {
+            self.show_help = !self.show_help;
+        }
+        ui::help_window(&ctx, &mut self.show_help);
+
+        let over_ui = ctx.is_pointer_over_area();
+        if !over_ui {
+            if let Some((l, i)) = hit(&self.layers, self.view.world_point(self.cursor)) {
+                ui::dot_tooltip(&ctx, &self.layers[l].dots[i]);
+            }
+        }
+        drop(ctx);
+
+        // Replays drive the simulation themselves, so only actions that leave it alone apply
+        if self.replay.is_some() {
+            actions = ui::Actions {
+                dump: actions.dump,
+                export_csv: actions.export_csv,
+                reset_view: actions.reset_view,
+                save_scene: actions.save_scene,
+                toggle_help: actions.toggle_help,
+                step: actions.step,
+                ..Default::default()
+            };
+        }
+
+        let target = self.scene.target_frame_ms / 1000.0;
+        self.quality.update(update.since_last.as_secs_f32(), target, self.scene.auto_quality);
+
+        let rect = self.window_rect;
+        for layer in self.layers.iter_mut() {
+            layer.approach_cap(self.quality.scale_cap(layer.settings.max_dots(rect)), update.since_last.as_secs_f32());
+            layer.bounds = rect;
+            layer.restyle();
+        }
+
+        if actions.dump {
+            self.dump();
+        }
+
+        if actions.export_csv {
+            self.export_csv();
+        }
+
+        if actions.save_scene {
+            self.save_scene();
+        }
+
+        if actions.load_scene {
+            self.load_scene();
+        }
+
+        if actions.clear {
+            self.clear(actions.clear_instantly);
+        }
+
+        if actions.reset {
+            self.reset();
+            if actions.clear_instantly {
+                self.clear(true);
+            }
+        }
+
+        if actions.reset_view {
+            self.view = View::default();
+        }
+
+        if actions.fill {
+            self.fill(self.active);
+        }
+
+        // Scrubbing pauses so the restored moment stays on screen
+        if let Some(offset) = actions.rewind {
+            self.history.restore(offset, &mut self.layers);
+            self.scene.paused = true;
+        }
+
+        if actions.step {
+            self.step_requested = true;
+        }
+        // Only the simulation runs on scaled time, egui and the plots stay on real time
+        let Some(delta) = sim_delta(&self.scene, update.since_last, &mut self.step_requested) else {
+            return
+        };
+
+        if self.replay.is_some() {
+            self.replay_frame();
+        } else {
+            // The cursor only pulls on the bubbles while it is over the field
+            let attracting = self.layers.iter().any(|l| l.settings.attraction != 0.0);
+            let attractor = (attracting && !over_ui).then(|| self.view.world_point(self.cursor));
+            self.step(delta, rect, attractor);
+
+            if self.emitting && !over_ui {
+                self.emit_carry += self.scene.emit_rate * delta.as_secs_f32();
+                let pivot = self.view.world_point(self.window_rect.xy());
+                while self.emit_carry >= 1.0 {
+                    self.spawn(self.active, self.view.world_point(self.cursor), pivot);
+                    self.emit_carry -= 1.0;
+                }
+            }
+        }
+
+        self.history.record(&self.layers);
+        self.peak = self.peak.max(self.dots().count());
+
+        if self.population.len() >= POPULATION_HISTORY {
+            self.population.pop_front();
+        }
+        self.population.push_back((update.since_start.as_secs_f64(), self.dots().count()));
+    }
+}
+
+/// Simulated time to advance this frame, scaled from `since_last`.
+///
+/// Nothing passes while paused, except a single frame when `step` is set, which is consumed.
+fn sim_delta(scene: &Scene, since_last: Duration, step: &mut bool) -> Option<Duration> {
+    let single = std::mem::take(step);
+    let scale = scene.time_scale.max(0.0);
+    if (scene.paused && !single) || scale == 0.0 {
+        return None
+    }
+    Some(since_last.mul_f32(scale))
+}
+
+/// Apply `event` to `layers`, advancing `sim_time` on steps, after logging it.
+///
+/// Live input and replays both come through here. Settings that changed since
+/// the last event are logged ahead of it, so a replay applies them in the same
+/// order even when nothing stepped in between.
+fn apply_event(layers: &mut [Layer], sim_time: &mut Duration, log: Option<&mut EventLog>, event: Event) {
+    if let Some(log) = log {
+        log.settings(*sim_time, layers.iter().map(|l| l.settings));
+        // Replayed settings are picked up by the diff before the next event instead
+        if !matches!(event, Event::Settings { .. }) {
+            log.write(*sim_time, event.clone());
+        }
+    }
+
+    match event {
+        Event::Seed(_) | Event::Layers(_) => {}
+        Event::Step { delta, size, caps, attractor } => {
+            let rect = Rect::from_w_h(size[0], size[1]);
+            *sim_time += delta;
+            let update = Update { since_last: delta, since_start: *sim_time };
+            for (layer, cap) in layers.iter_mut().zip(caps) {
+                layer.cap = cap;
+            }
+            for layer in layers.iter_mut() {
+                layer.attractor = attractor.map(Into::into);
+                layer.update_with_bounds(&update, rect);
+            }
+        }
+        Event::Spawn { layer, at, pivot } => {
+            if let Some(l) = layers.get_mut(layer) {
+                l.spawn_at(at.into(), pivot.into());
+            }
+        }
+        Event::Freeze { layer, index } => {
+            if let Some(dot) = layers.get_mut(layer).and_then(|l| l.dots.get_mut(index)) {
+                dot.frozen = !dot.frozen;
+            }
+        }
+        Event::Clear { instant } => {
+            for layer in layers.iter_mut() {
+                if instant {
+                    layer.dots.clear();
+                } else {
+                    layer.dots.iter_mut().for_each(|d| d.clearing = true);
+                }
+            }
+        }
+        Event::Fill { layer, cap } => {
+            if let Some(l) = layers.get_mut(layer) {
+                l.cap = cap;
+                l.fill();
+            }
+        }
+        Event::Settings { layer, settings } => match layers.get_mut(layer) {
+            Some(l) => l.settings = *settings,
+            None => warn!("Replay has settings for missing layer {}", layer + 1),
+        },
+    }
+}
+
+impl Model {
+    /// Advance every layer by `delta` of simulated time within `rect`,
+    /// pulled towards `attractor` by any layer with attraction on
+    fn step(&mut self, delta: Duration, rect: Rect, attractor: Option<Point>) {
+        self.sample_age();
+        self.apply(Event::Step {
+            delta,
+            size: rect.wh().into(),
+            caps: self.layers.iter().map(|l| l.cap).collect(),
+            attractor: attractor.map(Into::into),
+        });
+    }
+
+    /// Push the mean dot age onto the ring plot
+    fn sample_age(&mut self) {
+        let (count, total_age) = self.dots()
+            .fold((0, 0.0), |(n, s), d| (n + 1, s + d.age.as_secs_f32()));
+        let avg_age = if count > 0 { total_age / 10.0 / count as f32 } else { 0.0 };
+
+        self.ring_buf.pop_back();
+        self.ring_buf.push_front(avg_age);
+    }
+
+    /// Apply the replay's events up to its next step, then return to live input once it runs out
+    fn replay_frame(&mut self) {
+        let Some(replay) = &mut self.replay else { return };
+        let events = replay.next_frame();
+        let mut bounds = replay.bounds;
+        if events.is_empty() {
+            info!("Replay finished");
+            self.replay = None;
+            return
+        }
+
+        for event in events {
+            match event {
+                Event::Step { size, .. } => {
+                    let rect = Rect::from_w_h(size[0], size[1]);
+                    if bounds != Some(rect) {
+                        self.nudge_into(rect);
+                        bounds = Some(rect);
+                    }
+                    self.sample_age();
+                    self.apply(event);
+                }
+                // Layers added or removed mid-session can't be recreated
+                Event::Layers(count) if count != self.layers.len() => {
+                    warn!("Replay switches to {} layers, which can't be reproduced, stopping", count);
+                    self.replay = None;
+                    return
+                }
+                event => self.apply(event),
+            }
+        }
+
+        self.layers.iter_mut().for_each(Layer::restyle);
+        if let Some(replay) = &mut self.replay {
+            replay.bounds = bounds;
+        }
+    }
+
+    fn apply(&mut self, event: Event) {
+        apply_event(&mut self.layers, &mut self.sim_time, self.event_log.as_mut(), event);
+    }
+
+    fn spawn(&mut self, layer: usize, at: Point, pivot: Point) {
+        self.apply(Event::spawn(layer, at, pivot));
+    }
+
+    fn toggle_freeze(&mut self, layer: usize, index: usize) {
+        self.apply(Event::Freeze { layer, index });
+    }
+
+    fn fill(&mut self, layer: usize) {
+        self.apply(Event::Fill { layer, cap: self.layers[layer].cap });
+    }
+
+    pub fn trails(&self) -> bool {
+        self.scene.trail_persistence > 0.0
+    }
+
+    /// Render the drawing over the persistent scene and copy it into the frame,
+    /// restarting the trails whenever the frame size changes
+    pub fn render_trails(&self, draw: &Draw, scale_factor: f32, frame: &Frame) {
+        let mut trail = self.trail.borrow_mut();
+        if !trail.as_ref().is_some_and(|t| t.fits(frame)) {
+            *trail = Some(Trail::new(frame));
+        }
+        if let Some(trail) = trail.as_mut() {
+            trail.render(draw, scale_factor, frame);
+        }
+    }
+
+    /// Restore the startup scene and the active layer's startup settings,
+    /// keeping the dots and pause state
+    pub fn reset(&mut self) {
+        let paused = self.scene.paused;
+        self.scene = Scene { paused, ..self.defaults.scene };
+
+        let layer = &mut self.layers[self.active];
+        layer.settings = self.defaults.layers.get(self.active).copied().unwrap_or_default();
+    }
+
+    /// Current settings in their persisted form
+    pub fn config(&self) -> Config {
+        Config {
+            scene: self.scene,
+            layers: self.layers.iter().map(|l| l.settings).collect(),
+        }
+    }
+
+    /// All dots across every layer
+    pub fn dots(&self) -> impl Iterator<Item = &Dot> {
+        self.layers.iter().flat_map(|l| l.dots.iter())
+    }
+
+    /// Release anything that must not be cut off by the process exiting:
+    /// flush writers, finalize recordings and stop background threads.
+    ///
+    /// Safe to call more than once; only the first call has any effect.
+    pub fn shutdown(&mut self) {
+        if self.shut_down {
+            return
+        }
+        self.shut_down = true;
+
+        info!("Shutting down");
+        self.finish_recording();
+        if let Some(log) = &mut self.event_log {
+            log.flush();
+        }
+        self.report();
+    }
+
+    /// Print session totals across every layer
+    pub fn report(&self) {
+        let mut tally = Tally::default();
+        self.layers.iter().for_each(|l| tally += l.tally);
+
+        println!("Spawned {} dots, {} popped and {} expired", tally.spawned, tally.popped, tally.expired);
+        println!("Peak of {} dots at once, mean max radius {:.1}", self.peak, tally.mean_max_radius());
+    }
+
+    /// Print debugging data and save the config, if one was given
+    pub fn dump(&self) {
+        dbg!(&self.scene, &self.layers);
+
+        if let Some(path) = &OPTS.config {
+            if let Err(e) = self.config().save_to(path) {
+                warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Current scene and every layer's dots
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            scene: self.scene,
+            layers: self.layers.iter()
+                .map(|l| LayerSnapshot { settings: l.settings, dots: l.dots.clone() })
+                .collect(),
+        }
+    }
+
+    pub fn save_scene(&self) {
+        let path = scene_path();
+        match self.snapshot().save_to(&path) {
+            Ok(()) => info!("Saved scene to {}", path.display()),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    /// Replace the scene from the scene file, leaving everything as it was if it can't be read
+    pub fn load_scene(&mut self) {
+        match Snapshot::load_from(&scene_path()) {
+            Ok(snapshot) => self.restore(snapshot),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    /// Continue from a snapshot, keeping the pause state.
+    ///
+    /// Existing layers are reused so their random streams carry on, and extra
+    /// ones are forked from the last.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        let paused = self.scene.paused;
+        self.scene = Scene { paused, ..snapshot.scene };
+
+        self.layers.truncate(snapshot.layers.len());
+        while self.layers.len() < snapshot.layers.len() {
+            let last = self.layers.len() - 1;
+            let layer = self.layers[last].fork();
+            self.layers.push(layer);
+        }
+        for (layer, saved) in self.layers.iter_mut().zip(snapshot.layers) {
+            layer.settings = saved.settings;
+            layer.dots = saved.dots;
+            layer.next_serial = layer.dots.iter().map(|d| d.serial + 1).max().unwrap_or(0);
+            layer.particles.clear();
+        }
+        self.active = self.active.min(self.layers.len() - 1);
+        // Older snapshots are of a different scene
+        self.history = History::default();
+    }
+
+    /// Save the active layer's histograms to a timestamped CSV
+    pub fn export_csv(&self) {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let path = format!("histograms-{}.csv", millis);
+
+        let layer = &self.layers[self.active];
+        match stats::export_csv(&layer.dots, &layer.settings, Path::new(&path)) {
+            Ok(()) => info!("Exported histograms to {}", path),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    /// Top the active layer up to its cap at once, like Fill. Ignored while replaying.
+    pub fn burst(&mut self) {
+        if self.replay.is_none() {
+            self.fill(self.active);
+        }
+    }
+
+    /// Remove every dot, shrinking them away unless `instant`.
+    ///
+    /// Dots can't shrink while paused, so a paused clear is always instant.
+    /// Ignored while replaying.
+    pub fn clear(&mut self, instant: bool) {
+        if self.replay.is_none() {
+            self.remove_dots(instant || self.scene.paused);
+        }
+    }
+
+    fn remove_dots(&mut self, instant: bool) {
+        self.apply(Event::Clear { instant });
+    }
+
+    /// Write out the recording, even if cut short. Capture jobs must have completed.
+    pub fn finish_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if let Err(e) = recording.encode(Path::new(recording::OUTPUT)) {
+                warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Toggle freezing of the dot under `point`, or spawn a new one there on the
+    /// active layer orbiting the window center and keep emitting until released.
+    /// Ignored while replaying.
+    pub fn click(&mut self, point: Point) {
+        if self.replay.is_some() {
+            return
+        }
+
+        let point = self.view.world_point(point);
+        match hit(&self.layers, point) {
+            Some((l, i)) => self.toggle_freeze(l, i),
+            None => {
+                let pivot = self.view.world_point(self.window_rect.xy());
+                self.spawn(self.active, point, pivot);
+                self.emitting = true;
+                self.emit_carry = 0.0;
+            }
+        }
+    }
+
+    /// Loop mode for the frame rate limit: a fixed rate while limiting, otherwise
+    /// synced to the display refresh.
+    ///
+    /// Updates measure time with `since_last`, so a lower rate only changes how
+    /// often the scene is stepped and drawn, not how fast it moves.
+    pub fn loop_mode(&self) -> LoopMode {
+        if self.scene.limit_fps && self.scene.max_fps > 0.0 {
+            LoopMode::rate_fps(self.scene.max_fps.into())
+        } else {
+            LoopMode::refresh_sync()
+        }
+    }
+
+    /// Title the window should change to, if any.
+    ///
+    /// The status line is refreshed at most every `TITLE_INTERVAL`, and the base
+    /// title comes back as soon as it is turned off.
+    pub fn status_title(&mut self, since_last: Duration) -> Option<String> {
+        self.title_age += since_last;
+        let title = if self.scene.status_title {
+            if self.title_age < TITLE_INTERVAL {
+                return None
+            }
+            self.title_age = Duration::ZERO;
+
+            let count: usize = self.layers.iter().map(|l| l.dots.len()).sum();
+            let mut title = format!("{} | {} dots", self.base_title, count);
+            if self.scene.title_coverage {
+                let coverage: f32 = self.layers.iter().map(Layer::coverage).sum();
+                title += &format!(", {:.0}% coverage", coverage * 100.0);
+            }
+            title
+        } else {
+            self.base_title.clone()
+        };
+
+        if title == self.shown_title {
+            return None
+        }
+        self.shown_title = title.clone();
+        Some(title)
+    }
+
+    /// Pause on losing focus, and resume on regaining it if the pause was ours
+    pub fn focus(&mut self, focused: bool) {
+        if !focused {
+            if self.scene.pause_on_blur && !self.scene.paused {
+                self.scene.paused = true;
+                self.auto_paused = true;
+            }
+        } else if std::mem::take(&mut self.auto_paused) && self.scene.resume_on_focus {
+            self.scene.paused = false;
+        }
+    }
+
+    /// Record new window bounds and pull any stranded dots back inside,
+    /// unless replaying, where dots follow the recorded bounds instead
+    pub fn resize(&mut self, rect: Rect) {
+        self.window_rect = rect;
+        if self.replay.is_none() {
+            self.nudge_into(rect);
+        }
+    }
+
+    fn nudge_into(&mut self, rect: Rect) {
+        self.layers.iter_mut()
+            .flat_map(|l| l.dots.iter_mut())
+            .for_each(|d| d.nudge_into(rect));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, SeedableRng};
+    use crate::settings::Settings;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    fn layer() -> Layer {
+        let mut layer = Layer::new(Settings { max_count: 20, ..Settings::default() }, StdRng::seed_from_u64(3));
+        layer.update_with_bounds(&Update { since_last: FRAME, since_start: FRAME }, Rect::from_w_h(400.0, 400.0));
+        layer
+    }
+
+    fn advance(layer: &mut Layer, delta: Option<Duration>) {
+        if let Some(delta) = delta {
+            layer.update_with_bounds(&Update { since_last: delta, since_start: delta }, Rect::from_w_h(400.0, 400.0));
+        }
+    }
+
+    fn dots(layer: &Layer) -> String {
+        serde_json::to_string(&layer.dots).unwrap()
+    }
+
+    #[test]
+    fn sim_delta_stops_while_paused_or_at_zero_scale() {
+        let running = Scene { paused: false, time_scale: 2.0, ..Scene::default() };
+        let paused = Scene { paused: true, ..running };
+        let stopped = Scene { time_scale: 0.0, ..running };
+
+        let mut step = false;
+        assert_eq!(sim_delta(&running, FRAME, &mut step), Some(FRAME.mul_f32(2.0)));
+        for _ in 0..10 {
+            assert_eq!(sim_delta(&paused, FRAME, &mut step), None);
+        }
+        assert_eq!(sim_delta(&stopped, FRAME, &mut step), None);
+        // A single step can't advance time that is stopped by the scale
+        step = true;
+        assert_eq!(sim_delta(&stopped, FRAME, &mut step), None);
+        assert!(!step);
+    }
+
+    #[test]
+    fn paused_single_step_advances_one_frame() {
+        let scene = Scene { paused: true, ..Scene::default() };
+        let running = Scene { paused: false, ..scene };
+        let (mut stepped, mut live) = (layer(), layer());
+
+        let mut step = true;
+        advance(&mut stepped, sim_delta(&scene, FRAME, &mut step));
+        assert!(!step);
+        advance(&mut live, sim_delta(&running, FRAME, &mut false));
+        assert_eq!(dots(&stepped), dots(&live));
+        assert_ne!(dots(&stepped), dots(&layer()));
+
+        // The step is used up, so the following frames stay paused
+        for _ in 0..10 {
+            advance(&mut stepped, sim_delta(&scene, FRAME, &mut step));
+        }
+        assert_eq!(dots(&stepped), dots(&live));
+    }
+
+    fn step_event() -> Event {
+        Event::Step { delta: FRAME, size: [400.0, 400.0], caps: vec![20, 20], attractor: None }
+    }
+
+    fn fresh_layers() -> Vec<Layer> {
+        (0..2).map(|i| Layer::new(Settings::default(), StdRng::seed_from_u64(i))).collect()
+    }
+
+    #[test]
+    fn replaying_a_log_reproduces_the_session() {
+        let path = std::env::temp_dir().join(format!("nnndots-replay-{}.jsonl", std::process::id()));
+        let mut log = EventLog::create(&path).unwrap();
+        let mut live = fresh_layers();
+        let mut time = Duration::ZERO;
+
+        apply_event(&mut live, &mut time, Some(&mut log), step_event());
+        // Slider changes followed by a fill or spawn with no step in between
+        live[0].settings.growth_scale = 5.0;
+        apply_event(&mut live, &mut time, Some(&mut log), Event::Fill { layer: 0, cap: 10 });
+        live[1].settings.spawn_scale = 0.2;
+        apply_event(&mut live, &mut time, Some(&mut log), Event::spawn(1, vec2(10.0, 20.0), Vec2::ZERO));
+        for _ in 0..5 {
+            apply_event(&mut live, &mut time, Some(&mut log), step_event());
+        }
+        apply_event(&mut live, &mut time, Some(&mut log), Event::Freeze { layer: 0, index: 2 });
+        apply_event(&mut live, &mut time, Some(&mut log), step_event());
+        log.flush();
+
+        let mut replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.layers(), Some(2));
+
+        let mut replayed = fresh_layers();
+        let mut replay_time = Duration::ZERO;
+        loop {
+            let events = replay.next_frame();
+            if events.is_empty() {
+                break
+            }
+            events.into_iter().for_each(|e| apply_event(&mut replayed, &mut replay_time, None, e));
+        }
+
+        assert_eq!(replay_time, time);
+        assert!(live[0].dots[2].frozen);
+        for (live, replayed) in live.iter().zip(&replayed) {
+            assert!(!live.dots.is_empty());
+            assert_eq!(dots(replayed), dots(live));
+            assert_eq!(replayed.settings.growth_scale, live.settings.growth_scale);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+use nannou::{rand::Rng, prelude::*};
+
+use crate::color::{Rgba, scale_alpha};
+use crate::dot::{Nannou, Point};
+
+/// Upper bound on live particles per layer, regardless of how many dots pop at once
+pub const MAX_PARTICLES: usize = 2000;
+
+/// Diameter of a particle in pixels
+const PARTICLE_SIZE: f32 = 4.0;
+
+/// Tiny short-lived fleck thrown off when a bubble pops
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    origin: Point,
+    velocity: Vec2,
+    color: Rgba,
+    ttl: Duration,
+    lifetime: Duration,
+}
+
+impl Nannou for Particle {
+    fn display(&self, draw: &Draw) {
+        let fade = self.ttl.as_secs_f32() / self.lifetime.as_secs_f32();
+        draw.ellipse()
+            .color(scale_alpha(self.color, fade))
+            .w_h(PARTICLE_SIZE, PARTICLE_SIZE)
+            .xy(self.origin);
+    }
+
+    fn update(&mut self, update: &Update) {
+        self.ttl = self.ttl.saturating_sub(update.since_last);
+        self.origin += self.velocity * update.since_last.as_secs_f32();
+    }
+}
+
+impl Particle {
+    pub fn alive(&self) -> bool {
+        self.ttl > Duration::ZERO
+    }
+
+    /// Scatter `count` particles radially outward from `origin`
+    pub fn burst(rng: &mut impl Rng, origin: Point, color: Rgba, count: usize) -> impl Iterator<Item = Particle> + '_ {
+        (0..count).map(move |_| {
+            let angle = rng.gen_range(0.0..TAU);
+            let speed = rng.gen_range(100.0..300.0);
+            let lifetime = Duration::from_secs_f32(rng.gen_range(0.3..0.6));
+            Particle {
+                origin,
+                velocity: vec2(speed, 0.0).rotate(angle),
+                color,
+                ttl: lifetime,
+                lifetime,
+            }
+        })
+    }
+}
@@ -0,0 +1,56 @@
+/// Weight of the newest frame in the smoothed frame time
+const SMOOTHING: f32 = 0.05;
+
+/// Shed load once frames run this much over target...
+const SHED_ABOVE: f32 = 1.1;
+/// ...and only restore it once they are comfortably under, to avoid oscillating
+const RESTORE_BELOW: f32 = 0.8;
+
+/// Fraction of quality lost or regained per second while outside the band
+const SHED_RATE: f32 = 0.5;
+const RESTORE_RATE: f32 = 0.25;
+
+/// Never cut the population below this fraction of its cap
+const MIN_LEVEL: f32 = 0.1;
+
+/// Adaptive load shedding driven by smoothed frame time
+#[derive(Debug, Clone, Copy)]
+pub struct AutoQuality {
+    /// Exponentially smoothed frame time in seconds
+    pub frame_time: f32,
+    /// Fraction of each layer's cap currently allowed, 1 at full quality
+    pub level: f32,
+}
+
+impl Default for AutoQuality {
+    fn default() -> Self {
+        AutoQuality {
+            frame_time: 0.0,
+            level: 1.0,
+        }
+    }
+}
+
+impl AutoQuality {
+    /// Fold in the latest frame and adjust quality against a target in seconds
+    pub fn update(&mut self, delta: f32, target: f32, enabled: bool) {
+        self.frame_time += SMOOTHING * (delta - self.frame_time);
+
+        if !enabled {
+            self.level = 1.0;
+        } else if self.frame_time > target * SHED_ABOVE {
+            self.level = (self.level - SHED_RATE * delta).max(MIN_LEVEL);
+        } else if self.frame_time < target * RESTORE_BELOW {
+            self.level = (self.level + RESTORE_RATE * delta).min(1.0);
+        }
+    }
+
+    /// Expensive extras like gradients are dropped whenever quality is reduced
+    pub fn degraded(&self) -> bool {
+        self.level < 1.0
+    }
+
+    pub fn scale_cap(&self, cap: usize) -> usize {
+        (cap as f32 * self.level) as usize
+    }
+}
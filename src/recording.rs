@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use log::*;
+use nannou::image::{self, Delay, Frame, RgbaImage, codecs::gif::{GifEncoder, Repeat}};
+use nannou::window::Window;
+
+use crate::settings::OPTS;
+
+/// Where finished recordings are written
+pub const OUTPUT: &str = "bubbles.gif";
+
+/// Linear value of an sRGB-encoded channel, both scaled to `0..=255`
+fn srgb_to_linear(v: u8) -> u8 {
+    let v = v as f32 / 255.0;
+    let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+    (linear * 255.0).round() as u8
+}
+
+/// Replace the sRGB-encoded color channels of a captured frame with linear
+/// ones, leaving alpha alone.
+///
+/// Frames are captured as `Rgba8UnormSrgb`, the same encoding the window shows,
+/// so saved colors already match the screen. This is only for `--linear-capture`.
+pub fn linearize(image: &mut RgbaImage) {
+    let table: [u8; 256] = std::array::from_fn(|v| srgb_to_linear(v as u8));
+    for pixel in image.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = table[*c as usize];
+        }
+    }
+}
+
+/// Linearize a saved PNG in place
+pub fn linearize_file(path: &Path) -> Result<()> {
+    let mut image = image::open(path)
+        .with_context(|| format!("Reading {}", path.display()))?
+        .into_rgba8();
+    linearize(&mut image);
+    image.save(path)
+        .with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Fixed-length capture of the window, encoded as a GIF once complete.
+///
+/// Frames are spooled to PNGs in a temp directory rather than held in memory,
+/// and only every `every`th frame is kept to bound disk use and encoding time.
+#[derive(Debug)]
+pub struct Recording {
+    dir: PathBuf,
+    remaining: Duration,
+    every: usize,
+    frame: usize,
+    frames: Vec<PathBuf>,
+    /// Wall time spanned by the captured frames
+    elapsed: Duration,
+}
+
+impl Recording {
+    pub fn new(duration: Duration, every: usize) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("bubbles-{}", std::process::id()));
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Creating {}", dir.display()))?;
+
+        Ok(Recording {
+            dir,
+            remaining: duration,
+            every: every.max(1),
+            frame: 0,
+            frames: Vec::new(),
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    pub fn done(&self) -> bool {
+        self.remaining.is_zero()
+    }
+
+    /// Called once per update, capturing every Nth frame until time runs out
+    pub fn tick(&mut self, window: &Window, delta: Duration) {
+        if self.done() {
+            return
+        }
+
+        if self.frame.is_multiple_of(self.every) {
+            let path = self.dir.join(format!("frame-{:05}.png", self.frames.len()));
+            window.capture_frame(&path);
+            self.frames.push(path);
+        }
+
+        self.frame += 1;
+        self.elapsed += delta;
+        self.remaining = self.remaining.saturating_sub(delta);
+    }
+
+    /// Encode the captured frames into a looping GIF and remove the spool directory.
+    ///
+    /// Pending capture jobs must have completed before this is called.
+    pub fn encode(&self, out: &Path) -> Result<()> {
+        info!("Encoding {} frames to {}", self.frames.len(), out.display());
+
+        let delay = match self.frames.len() {
+            0 => Duration::ZERO,
+            n => self.elapsed / n as u32,
+        };
+        let delay = Delay::from_saturating_duration(delay);
+
+        let file = File::create(out)
+            .with_context(|| format!("Creating {}", out.display()))?;
+        let mut encoder = GifEncoder::new(BufWriter::new(file));
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        for path in &self.frames {
+            let mut buffer = image::open(path)
+                .with_context(|| format!("Reading {}", path.display()))?
+                .into_rgba8();
+            if OPTS.linear_capture {
+                linearize(&mut buffer);
+            }
+            encoder.encode_frame(Frame::from_parts(buffer, 0, 0, delay))?;
+        }
+
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            warn!("Unable to remove {}: {}", self.dir.display(), e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+
+    /// A solid mid-gray capture: the window shows sRGB 128, roughly 22% of full
+    /// intensity. Saved as captured it reads back as 128 like the screen, and
+    /// linearized for `--linear-capture` it reads back as 55.
+    #[test]
+    fn linear_capture_of_solid_gray() {
+        let path = std::env::temp_dir().join(format!("nnndots-gray-{}.png", std::process::id()));
+        RgbaImage::from_pixel(4, 4, Rgba([128, 128, 128, 200])).save(&path).unwrap();
+        let before = image::open(&path).unwrap().into_rgba8();
+
+        linearize_file(&path).unwrap();
+        let after = image::open(&path).unwrap().into_rgba8();
+        fs::remove_file(&path).unwrap();
+
+        assert!(before.pixels().all(|p| p.0 == [128, 128, 128, 200]));
+        assert!(after.pixels().all(|p| p.0 == [55, 55, 55, 200]));
+    }
+
+    #[test]
+    fn linearizing_keeps_black_and_white() {
+        let mut image = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+        linearize(&mut image);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 255, 255, 255]);
+    }
+}
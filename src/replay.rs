@@ -0,0 +1,169 @@
+//! Line-delimited JSON log of the inputs that drive the simulation, so a
+//! session can be played back exactly with `--replay`.
+//!
+//! Only what reaches the simulation is logged: the seed, each step's scaled
+//! time and bounds, spawns, freezes, clears, fills, settings changes and the
+//! layer count. Rewinds are not logged, so a replay of a session that used them
+//! diverges from that point, and a replay stops where layers were added or removed.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use log::*;
+use nannou::geom::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::dot::Point;
+use crate::settings::Settings;
+
+/// Something that changed the simulation, in the order it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Event {
+    /// Seed every layer was created from, always first
+    Seed(u64),
+    /// Number of layers, logged before the first settings and whenever it changes
+    Layers(usize),
+    /// Advance the simulation, within bounds of this size, with these dot caps
+    /// by layer and with the cursor pulling from `attractor`
+    Step {
+        delta: Duration,
+        size: [f32; 2],
+        caps: Vec<usize>,
+        #[serde(default)]
+        attractor: Option<[f32; 2]>,
+    },
+    /// Dot added by a click or by emission, in world coordinates
+    Spawn { layer: usize, at: [f32; 2], pivot: [f32; 2] },
+    /// Frozen state of a dot toggled by clicking on it
+    Freeze { layer: usize, index: usize },
+    /// Dots removed, at once or by shrinking away
+    Clear { instant: bool },
+    /// Layer topped up to this many dots
+    Fill { layer: usize, cap: usize },
+    /// New settings for a layer
+    Settings { layer: usize, settings: Box<Settings> },
+}
+
+impl Event {
+    pub fn spawn(layer: usize, at: Point, pivot: Point) -> Self {
+        Event::Spawn { layer, at: at.into(), pivot: pivot.into() }
+    }
+}
+
+/// One line of the log, stamped with the simulated time it happened at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub secs: f64,
+    pub event: Event,
+}
+
+/// Writes events to a `--log-events` file as they happen
+#[derive(Debug)]
+pub struct EventLog {
+    out: BufWriter<File>,
+    /// Settings of each layer as last logged, serialized, to log only changes
+    logged: Vec<String>,
+}
+
+impl EventLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Creating {}", path.display()))?;
+        Ok(EventLog { out: BufWriter::new(file), logged: Vec::new() })
+    }
+
+    pub fn write(&mut self, sim_time: Duration, event: Event) {
+        let entry = Entry { secs: sim_time.as_secs_f64(), event };
+        let written = serde_json::to_writer(&mut self.out, &entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(self.out.write_all(b"\n")?));
+        if let Err(e) = written {
+            warn!("Writing event log: {:#}", e);
+        }
+    }
+
+    /// Log the settings of any layer that changed since they were last logged,
+    /// after the layer count if that changed
+    pub fn settings(&mut self, sim_time: Duration, settings: impl ExactSizeIterator<Item = Settings>) {
+        if settings.len() != self.logged.len() {
+            self.logged.truncate(settings.len());
+            self.write(sim_time, Event::Layers(settings.len()));
+        }
+        for (layer, settings) in settings.enumerate() {
+            let Ok(json) = serde_json::to_string(&settings) else { continue };
+            if self.logged.get(layer) == Some(&json) {
+                continue
+            }
+            if layer < self.logged.len() {
+                self.logged[layer] = json;
+            } else {
+                self.logged.push(json);
+            }
+            self.write(sim_time, Event::Settings { layer, settings: Box::new(settings) });
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(e) = self.out.flush() {
+            warn!("Flushing event log: {}", e);
+        }
+    }
+}
+
+/// Events loaded from a `--replay` file, fed back one step at a time
+#[derive(Debug)]
+pub struct Replay {
+    pub events: VecDeque<Event>,
+    /// Bounds of the last step replayed
+    pub bounds: Option<Rect>,
+}
+
+impl Replay {
+    /// Read a log, failing on the first malformed line since everything after it would diverge
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Reading {}", path.display()))?;
+
+        let events = text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str::<Entry>(line)
+                    .map(|entry| entry.event)
+                    .with_context(|| format!("{}:{}: invalid event", path.display(), i + 1))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Replay { events, bounds: None })
+    }
+
+    /// Seed the log was recorded with, if it starts with one
+    pub fn seed(&self) -> Option<u64> {
+        match self.events.front() {
+            Some(&Event::Seed(seed)) => Some(seed),
+            _ => None,
+        }
+    }
+
+    /// Layer count the log was recorded with, if it has one
+    pub fn layers(&self) -> Option<usize> {
+        self.events.iter().find_map(|event| match event {
+            &Event::Layers(count) => Some(count),
+            _ => None,
+        })
+    }
+
+    /// Events up to and including the next step, empty once the log is exhausted
+    pub fn next_frame(&mut self) -> Vec<Event> {
+        let mut frame = Vec::new();
+        while let Some(event) = self.events.pop_front() {
+            let step = matches!(event, Event::Step { .. });
+            frame.push(event);
+            if step {
+                break
+            }
+        }
+        frame
+    }
+}
@@ -0,0 +1,695 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ops::RangeInclusive;
+use anyhow::{bail, Context, Result};
+use log::*;
+use structopt::StructOpt;
+use lazy_static::lazy_static;
+use nannou::geom::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{self, luminance, ChannelRanges, Color, Harmony, Rgb};
+use crate::dot::{ArenaShape, ColorMode, Easing, ShapePool, SpawnRegion, Style, MAX_STRETCH};
+use crate::sim::{Dist, TtlDist, MIN_TTL};
+
+#[derive(Debug, StructOpt)]
+pub struct Opts {
+    /// Maximum angular velocity in radians per second
+    #[structopt(short, long, default_value="1.0")]
+    pub speed: f32,
+
+    /// Maximum bubble growth rate in pixels per second
+    #[structopt(short, long, default_value="100.0")]
+    pub rate: f32,
+
+    /// Maximum bubbles to render simultaneously
+    #[structopt(short, long, default_value="1")]
+    pub num_dots: u16,
+
+    /// Leave the egui windows out of screenshots taken with S
+    #[structopt(long)]
+    pub hide_ui_on_capture: bool,
+
+    /// Save screenshots and recordings with linear channel values instead of the
+    /// sRGB-encoded ones shown on screen, for tools that expect linear data
+    #[structopt(long)]
+    pub linear_capture: bool,
+
+    /// Record this many seconds to bubbles.gif
+    #[structopt(long)]
+    pub record: Option<f32>,
+
+    /// Keep only every Nth frame while recording
+    #[structopt(long, default_value="2")]
+    pub record_every: usize,
+
+    /// Simulate this many frames without a window, then print timings and exit
+    #[structopt(long)]
+    pub headless: Option<usize>,
+
+    /// Draw the same scene for this many frames per ellipse and then batched,
+    /// offscreen, then print both timings and exit
+    #[structopt(long)]
+    pub bench_draw: Option<usize>,
+
+    /// Seed for reproducible runs, otherwise seeded from the OS
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// TOML file to load settings from at startup and save them to on Dump
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// File of `#RRGGBB` or `#RRGGBBAA` colors, one per line, that new dots are colored from
+    #[structopt(long, parse(from_os_str))]
+    pub palette: Option<PathBuf>,
+
+    /// Log what the simulation is fed to this file as line-delimited JSON, for --replay
+    #[structopt(long, parse(from_os_str))]
+    pub log_events: Option<PathBuf>,
+
+    /// Play back a --log-events file in place of live input, reproducing its session
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+
+    /// JSON scene to start from, also where Save scene and Load scene write and read
+    #[structopt(long, parse(from_os_str))]
+    pub scene: Option<PathBuf>,
+
+    /// Cap updates and redraws at this many per second with nannou's rate-limited
+    /// loop mode instead of syncing to the display refresh. Vsync still holds the
+    /// rate to the refresh rate, so only a lower cap has an effect. nannou 0.19
+    /// still runs the rate mode like refresh sync (nannou issue #456), so the cap
+    /// only takes hold with a nannou that implements it.
+    #[structopt(long)]
+    pub fps: Option<f32>,
+
+    /// Log the dot count, spawn rate and mean radius and speed every this many seconds
+    /// at info level, so with RUST_LOG=info. 0 disables.
+    #[structopt(long, default_value="10")]
+    pub stats_interval: f32,
+
+    /// Initial window width in points
+    #[structopt(long)]
+    pub width: Option<u32>,
+
+    /// Initial window height in points
+    #[structopt(long)]
+    pub height: Option<u32>,
+
+    /// Window title
+    #[structopt(long)]
+    pub title: Option<String>,
+}
+
+lazy_static! {
+    /// Tests get the defaults, since the harness's own arguments aren't ours
+    pub static ref OPTS: Opts = if cfg!(test) {
+        Opts::from_iter(["nnndots"])
+    } else {
+        Opts::from_args()
+    };
+}
+
+/// Slider limits in the settings window, which command line values are clamped to
+pub const SPEED_RANGE: RangeInclusive<f32> = 0.0..=10.0;
+pub const RATE_RANGE: RangeInclusive<f32> = 0.0..=1000.0;
+pub const COUNT_RANGE: RangeInclusive<u16> = 1..=5000;
+pub const FPS_RANGE: RangeInclusive<f32> = 1.0..=240.0;
+
+/// Limits of the remaining sliders, which configs and scenes are clamped to on load
+pub const TIME_SCALE_RANGE: RangeInclusive<f32> = 0.1..=5.0;
+pub const GRID_SPACING_RANGE: RangeInclusive<f32> = 10.0..=1000.0;
+pub const TRAIL_RANGE: RangeInclusive<f32> = 0.0..=0.99;
+pub const EMIT_RATE_RANGE: RangeInclusive<f32> = 0.0..=200.0;
+pub const FRAME_MS_RANGE: RangeInclusive<f32> = 5.0..=100.0;
+pub const QUALITY_RANGE: RangeInclusive<f32> = 0.25..=4.0;
+pub const FRACTION_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+pub const DENSITY_RANGE: RangeInclusive<f32> = 1.0..=500.0;
+pub const COVERAGE_RANGE: RangeInclusive<f32> = 0.05..=2.0;
+pub const SMOOTHING_RANGE: RangeInclusive<f32> = 0.0..=10.0;
+pub const SPAWN_PER_FRAME_RANGE: RangeInclusive<u8> = 1..=32;
+pub const SPAWN_SCALE_RANGE: RangeInclusive<f32> = 0.05..=1.0;
+pub const STRETCH_RANGE: RangeInclusive<f32> = 0.05..=MAX_STRETCH;
+pub const BIAS_RANGE: RangeInclusive<f32> = -1.0..=1.0;
+pub const DRIFT_RANGE: RangeInclusive<f32> = 0.0..=200.0;
+pub const ATTRACTION_RANGE: RangeInclusive<f32> = -1000.0..=1000.0;
+pub const GRAVITY_RANGE: RangeInclusive<f32> = -200.0..=200.0;
+/// Matches `RATE_RANGE`, which the default growth scale comes from
+pub const GROWTH_PARAM_RANGE: RangeInclusive<f32> = RATE_RANGE;
+pub const HUE_RATE_RANGE: RangeInclusive<f32> = -180.0..=180.0;
+pub const STROKE_RANGE: RangeInclusive<f32> = 0.5..=20.0;
+pub const SPACING_RANGE: RangeInclusive<f32> = 0.0..=200.0;
+pub const RETRIES_RANGE: RangeInclusive<u8> = 1..=50;
+pub const REPULSION_RANGE: RangeInclusive<f32> = 0.0..=500.0;
+pub const BIRTH_AGE_RANGE: RangeInclusive<f32> = 0.0..=5.0;
+pub const PARTICLE_RANGE: RangeInclusive<u8> = 1..=64;
+pub const ARENA_RANGE: RangeInclusive<f32> = 50.0..=1000.0;
+pub const TTL_RANGE: RangeInclusive<f32> = MIN_TTL..=60.0;
+pub const RADIUS_PARAM_RANGE: RangeInclusive<f32> = 1.0..=500.0;
+pub const COUPLING_RANGE: RangeInclusive<f32> = 1.0..=100.0;
+pub const BUCKET_RANGE: RangeInclusive<u8> = 1..=50;
+pub const SPAWN_IN_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// Accepted window sides in points, from tiny up to well past 8K
+pub const WINDOW_RANGE: RangeInclusive<u32> = 64..=16384;
+
+impl Opts {
+    /// Reject values that leave the app doing nothing useful.
+    ///
+    /// Values beyond the slider ranges are allowed but get clamped when settings are built.
+    pub fn validate(&self) -> Result<()> {
+        if self.num_dots == 0 {
+            bail!("--num-dots must be at least 1");
+        }
+        if !COUNT_RANGE.contains(&self.num_dots) {
+            warn!("--num-dots {} is outside {:?}, clamping", self.num_dots, COUNT_RANGE);
+        }
+        for (name, value, range) in [("--speed", self.speed, SPEED_RANGE), ("--rate", self.rate, RATE_RANGE)] {
+            if value.is_nan() || value < 0.0 {
+                bail!("{} must be a non-negative number, got {}", name, value);
+            }
+            if !range.contains(&value) {
+                warn!("{} {} is outside {:?}, clamping", name, value, range);
+            }
+        }
+        if let Some(fps) = self.fps {
+            if !(fps > 0.0 && fps.is_finite()) {
+                bail!("--fps must be a positive number, got {}", fps);
+            }
+            if !FPS_RANGE.contains(&fps) {
+                warn!("--fps {} is outside {:?}, clamping", fps, FPS_RANGE);
+            }
+        }
+        for (name, value) in [("--width", self.width), ("--height", self.height)] {
+            match value {
+                Some(v) if !WINDOW_RANGE.contains(&v) => bail!("{} must be within {:?}, got {}", name, WINDOW_RANGE, v),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options shared by the whole scene rather than a single layer
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    #[serde(skip)]
+    pub paused: bool,
+    #[serde(with = "color::hex")]
+    pub bg_color: Rgb,
+    /// Draw a vertical gradient from `bg_top` to `bg_bottom` instead of `bg_color`
+    pub gradient: bool,
+    #[serde(with = "color::hex")]
+    pub bg_top: Rgb,
+    #[serde(with = "color::hex")]
+    pub bg_bottom: Rgb,
+    /// Fraction of the previous frame kept each frame, leaving motion trails. 0 clears fully.
+    pub trail_persistence: f32,
+    /// Dots per second emitted from the cursor while the left button is held
+    pub emit_rate: f32,
+    /// Look of the egui windows
+    pub theme: Theme,
+    /// Draw world-space grid lines every `grid_spacing` behind the bubbles
+    pub show_grid: bool,
+    /// Draw a bar `grid_spacing` long at the current zoom
+    pub show_scale_bar: bool,
+    /// World distance between grid lines and the length of the scale bar
+    pub grid_spacing: f32,
+    /// Show the FPS and frame time window
+    pub show_fps: bool,
+    /// Show the bubble count over time
+    pub show_population: bool,
+    /// Gather the overlays into one scrollable side panel instead of floating windows
+    pub side_panel: bool,
+    /// Overlay each dot's velocity and pivot
+    pub debug_vectors: bool,
+    /// Include orbit circles in the debug overlay
+    pub debug_orbits: bool,
+    /// Shed population and extras when frames run over `target_frame_ms`
+    pub auto_quality: bool,
+    pub target_frame_ms: f32,
+    /// Multiplier on the simulated time step, for slow motion or fast forward
+    pub time_scale: f32,
+    /// Run nannou's loop at `max_fps` rather than at the display refresh
+    pub limit_fps: bool,
+    pub max_fps: f32,
+    /// Pause when the window loses focus
+    pub pause_on_blur: bool,
+    /// Undo a pause on focus loss once focus returns. Manual pauses are never undone.
+    pub resume_on_focus: bool,
+    /// Show the dot count in the window title, and with `title_coverage` the coverage too
+    pub status_title: bool,
+    pub title_coverage: bool,
+}
+
+/// Pull `value` into `range`, with NaN going to the low end
+fn clamp_to<T: PartialOrd + Copy>(value: &mut T, range: RangeInclusive<T>) {
+    let (lo, hi) = range.into_inner();
+    *value = match (*value).partial_cmp(&lo) {
+        Some(std::cmp::Ordering::Less) | None => lo,
+        _ if *value > hi => hi,
+        _ => *value,
+    };
+}
+
+/// Look of the egui windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Light over bright backgrounds, dark otherwise
+    Auto,
+}
+
+/// Background luminance above which the automatic theme turns light, about mid-gray
+const LIGHT_ABOVE: f32 = 0.18;
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Dark, Theme::Light, Theme::Auto];
+
+    /// Whether this theme is dark over the scene's background
+    pub fn dark(&self, scene: &Scene) -> bool {
+        match self {
+            Theme::Dark => true,
+            Theme::Light => false,
+            Theme::Auto if scene.gradient => (luminance(scene.bg_top) + luminance(scene.bg_bottom)) / 2.0 <= LIGHT_ABOVE,
+            Theme::Auto => luminance(scene.bg_color) <= LIGHT_ABOVE,
+        }
+    }
+}
+
+impl Scene {
+    /// Pull every value into the range its slider allows, for scenes read from files
+    pub fn clamp_ranges(&mut self) {
+        clamp_to(&mut self.time_scale, TIME_SCALE_RANGE);
+        clamp_to(&mut self.grid_spacing, GRID_SPACING_RANGE);
+        clamp_to(&mut self.trail_persistence, TRAIL_RANGE);
+        clamp_to(&mut self.emit_rate, EMIT_RATE_RANGE);
+        clamp_to(&mut self.max_fps, FPS_RANGE);
+        clamp_to(&mut self.target_frame_ms, FRAME_MS_RANGE);
+    }
+}
+
+impl Default for Scene {
+    /// Defaults, with the frame limit taken from the command line
+    fn default() -> Self {
+        Scene {
+            paused: false,
+            bg_color: Color::DimGray.into(),
+            gradient: false,
+            bg_top: Color::SteelBlue.into(),
+            bg_bottom: Color::DimGray.into(),
+            trail_persistence: 0.0,
+            emit_rate: 20.0,
+            theme: Theme::Dark,
+            show_grid: false,
+            show_scale_bar: false,
+            grid_spacing: 100.0,
+            show_fps: true,
+            show_population: false,
+            side_panel: false,
+            debug_vectors: false,
+            debug_orbits: false,
+            auto_quality: false,
+            target_frame_ms: 20.0,
+            time_scale: 1.0,
+            limit_fps: OPTS.fps.is_some(),
+            max_fps: OPTS.fps.unwrap_or(60.0).clamp(*FPS_RANGE.start(), *FPS_RANGE.end()),
+            pause_on_blur: false,
+            resume_on_focus: false,
+            status_title: false,
+            title_coverage: false,
+        }
+    }
+}
+
+/// Per-layer spawning, motion and rendering options
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub max_count: u16,
+    /// Frozen dots keep losing TTL, and die soon after being unfrozen
+    pub frozen_aging: bool,
+    /// Seconds for the cap to ease most of the way to a new maximum count, 0 changes it at once
+    pub cap_smoothing: f32,
+    /// Most dots added in a single frame while below the cap
+    pub spawn_per_frame: u8,
+    /// Where origins and pivots of new dots are placed
+    pub spawn_region: SpawnRegion,
+    /// Size of the spawn region relative to the window
+    pub spawn_scale: f32,
+    /// Further stretch of the spawn region along each axis, for bands and columns
+    pub spawn_stretch_x: f32,
+    pub spawn_stretch_y: f32,
+    /// Derive the maximum count from window area instead of `max_count`
+    pub density_mode: bool,
+    /// Bubbles per megapixel of window area in density mode
+    pub density: f32,
+    /// Stop spawning while dots cover more than `max_coverage` of the window
+    pub throttle_coverage: bool,
+    /// Fraction of window area, summed over all dots, that throttling holds below
+    pub max_coverage: f32,
+    pub max_speed: f32,
+    /// Odds of new dots orbiting counterclockwise, from -1 all clockwise to 1 all counterclockwise
+    pub orbit_bias: f32,
+    /// Fastest linear drift of a whole orbit in pixels per second
+    pub max_drift: f32,
+    /// Fastest wander of a pivot around its own slow orbit in pixels per second
+    pub pivot_wander: f32,
+    /// Downward acceleration of drift in pixels per second squared, negative to rise
+    pub gravity: f32,
+    /// Distribution of growth rates in pixels per second
+    pub growth_dist: Dist,
+    /// First parameter of `growth_dist`, as with `shape`
+    pub growth_shape: f32,
+    /// Second parameter of `growth_dist`, as with `scale`
+    pub growth_scale: f32,
+    pub radius_dist: Dist,
+    /// Second parameter of `radius_dist`: Gamma scale, Normal deviation or Uniform max
+    pub scale: f32,
+    /// First parameter of `radius_dist`: Gamma shape, Normal mean or Uniform min
+    pub shape: f32,
+    /// How lifetimes are spread between `ttl_min` and `ttl_max`
+    pub ttl_dist: TtlDist,
+    /// Shortest lifetime in seconds
+    pub ttl_min: f32,
+    /// Longest lifetime in seconds
+    pub ttl_max: f32,
+    /// Derive max radius from TTL instead of sampling `radius_dist`
+    pub couple_ttl_radius: bool,
+    /// Max radius in pixels per second of TTL when coupled
+    pub ttl_coupling: f32,
+    /// Spawn radius as a fraction of the sampled max radius
+    pub start_fraction: f32,
+    /// Pull of the cursor on dots in pixels per second² at 100 pixels away,
+    /// falling off with distance. Negative repels and 0 disables.
+    pub attraction: f32,
+    /// Least gap in pixels between a new dot's center and any existing dot's edge, 0 allows any
+    pub min_spacing: f32,
+    /// Positions tried per spawn before giving up on spawning for the frame
+    pub spacing_retries: u8,
+    /// Pixels per second that newborn dots push away from overlapping dots
+    pub birth_repulsion: f32,
+    /// Age in seconds below which dots count as newborn
+    pub birth_age: f32,
+    /// Coalesce overlapping dots into one with their combined area
+    pub merge: bool,
+    /// Bounce overlapping dots off each other elastically
+    pub collide: bool,
+    /// Throw off particles when a dot dies
+    pub pop_particles: bool,
+    /// Particles per popped dot
+    pub particle_count: u8,
+    pub arena: ArenaShape,
+    pub arena_radius: f32,
+    pub batch_draw: bool,
+    /// Buckets in the speed and radius histograms and their CSV export
+    pub histogram_buckets: u8,
+    /// Multiplier on the segments each ellipse is drawn with outside batch mode
+    pub ellipse_quality: f32,
+    /// Seconds new dots take to scale in from nothing when drawn, 0 disables
+    pub spawn_in: f32,
+    /// Draw larger dots first so smaller ones always blend over them
+    pub depth_sort: bool,
+    /// Fade bubbles as they approach their maximum radius
+    pub alpha_by_radius: bool,
+    /// Fraction of opacity lost at full size
+    pub alpha_strength: f32,
+    pub easing: Easing,
+    pub color_mode: ColorMode,
+    /// Channel ranges random colors of new dots are drawn from
+    pub channels: ChannelRanges,
+    /// Bias random colors of new dots towards `tint`
+    pub tinted: bool,
+    /// Hues around the tint that tinted colors are drawn from
+    pub harmony: Harmony,
+    #[serde(with = "color::hex")]
+    pub tint: Rgb,
+    /// Hue rotation in degrees per second of simulated time, 0 disables
+    pub hue_rate: f32,
+    /// Only rotate the hue of dots as they spawn, instead of cycling every dot
+    pub hue_new_only: bool,
+    /// Shapes new dots are drawn from
+    pub shapes: ShapePool,
+    /// Outline newly spawned dots
+    pub stroke: bool,
+    pub stroke_weight: f32,
+    #[serde(with = "color::hex")]
+    pub stroke_color: Rgb,
+    /// Fill newly spawned dots, only optional while stroking
+    pub fill: bool,
+}
+
+impl Default for Settings {
+    /// Defaults derived from the command line
+    fn default() -> Self {
+        Settings {
+            max_count: OPTS.num_dots.clamp(*COUNT_RANGE.start(), *COUNT_RANGE.end()),
+            frozen_aging: false,
+            cap_smoothing: 0.0,
+            spawn_per_frame: 1,
+            throttle_coverage: false,
+            max_coverage: 0.5,
+            spawn_region: SpawnRegion::Square,
+            spawn_scale: 1.0,
+            spawn_stretch_x: 1.0,
+            spawn_stretch_y: 1.0,
+            density_mode: false,
+            density: 100.0,
+            max_speed: OPTS.speed.clamp(*SPEED_RANGE.start(), *SPEED_RANGE.end()),
+            orbit_bias: 0.0,
+            max_drift: 0.0,
+            pivot_wander: 0.0,
+            gravity: 0.0,
+            // Uniform from 1 up to --rate, as growth was sampled before it was configurable
+            growth_dist: Dist::Uniform,
+            growth_shape: 1.0,
+            growth_scale: OPTS.rate.clamp(*RATE_RANGE.start(), *RATE_RANGE.end()),
+            radius_dist: Dist::Gamma,
+            scale: 10.0,
+            shape: 10.0,
+            ttl_dist: TtlDist::Uniform,
+            ttl_min: 1.0,
+            ttl_max: 10.0,
+            couple_ttl_radius: false,
+            ttl_coupling: 20.0,
+            start_fraction: 0.1,
+            attraction: 0.0,
+            min_spacing: 0.0,
+            spacing_retries: 10,
+            birth_repulsion: 0.0,
+            merge: false,
+            collide: false,
+            birth_age: 0.5,
+            pop_particles: false,
+            particle_count: 12,
+            arena: ArenaShape::Rect,
+            arena_radius: 400.0,
+            batch_draw: false,
+            histogram_buckets: 10,
+            ellipse_quality: 1.0,
+            spawn_in: 0.0,
+            depth_sort: false,
+            alpha_by_radius: false,
+            alpha_strength: 0.7,
+            easing: Easing::Linear,
+            color_mode: ColorMode::Random,
+            channels: ChannelRanges::default(),
+            tinted: false,
+            harmony: Harmony::Mono,
+            tint: Color::SteelBlue.into(),
+            hue_rate: 0.0,
+            hue_new_only: false,
+            shapes: ShapePool::default(),
+            stroke: false,
+            stroke_weight: 2.0,
+            stroke_color: Color::Honeydew.into(),
+            fill: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Pull every value into the range its slider allows, for settings read from files
+    pub fn clamp_ranges(&mut self) {
+        clamp_to(&mut self.max_count, COUNT_RANGE);
+        clamp_to(&mut self.cap_smoothing, SMOOTHING_RANGE);
+        clamp_to(&mut self.spawn_per_frame, SPAWN_PER_FRAME_RANGE);
+        clamp_to(&mut self.spawn_scale, SPAWN_SCALE_RANGE);
+        clamp_to(&mut self.spawn_stretch_x, STRETCH_RANGE);
+        clamp_to(&mut self.spawn_stretch_y, STRETCH_RANGE);
+        clamp_to(&mut self.density, DENSITY_RANGE);
+        clamp_to(&mut self.max_coverage, COVERAGE_RANGE);
+        clamp_to(&mut self.max_speed, SPEED_RANGE);
+        clamp_to(&mut self.orbit_bias, BIAS_RANGE);
+        clamp_to(&mut self.max_drift, DRIFT_RANGE);
+        clamp_to(&mut self.pivot_wander, DRIFT_RANGE);
+        clamp_to(&mut self.gravity, GRAVITY_RANGE);
+        clamp_to(&mut self.growth_shape, GROWTH_PARAM_RANGE);
+        clamp_to(&mut self.growth_scale, GROWTH_PARAM_RANGE);
+        clamp_to(&mut self.shape, RADIUS_PARAM_RANGE);
+        clamp_to(&mut self.scale, RADIUS_PARAM_RANGE);
+        clamp_to(&mut self.ttl_min, TTL_RANGE);
+        clamp_to(&mut self.ttl_max, TTL_RANGE);
+        clamp_to(&mut self.ttl_coupling, COUPLING_RANGE);
+        clamp_to(&mut self.start_fraction, FRACTION_RANGE);
+        clamp_to(&mut self.attraction, ATTRACTION_RANGE);
+        clamp_to(&mut self.min_spacing, SPACING_RANGE);
+        clamp_to(&mut self.spacing_retries, RETRIES_RANGE);
+        clamp_to(&mut self.birth_repulsion, REPULSION_RANGE);
+        clamp_to(&mut self.birth_age, BIRTH_AGE_RANGE);
+        clamp_to(&mut self.particle_count, PARTICLE_RANGE);
+        clamp_to(&mut self.arena_radius, ARENA_RANGE);
+        clamp_to(&mut self.histogram_buckets, BUCKET_RANGE);
+        clamp_to(&mut self.ellipse_quality, QUALITY_RANGE);
+        clamp_to(&mut self.spawn_in, SPAWN_IN_RANGE);
+        clamp_to(&mut self.alpha_strength, FRACTION_RANGE);
+        clamp_to(&mut self.hue_rate, HUE_RATE_RANGE);
+        clamp_to(&mut self.stroke_weight, STROKE_RANGE);
+    }
+
+    pub fn style(&self) -> Style {
+        Style {
+            alpha_by_radius: if self.alpha_by_radius { self.alpha_strength } else { 0.0 },
+            color_mode: self.color_mode,
+            max_speed: self.max_speed,
+            hue_shift: 0.0,
+            ellipse_quality: self.ellipse_quality,
+            spawn_in: self.spawn_in,
+        }
+    }
+
+    /// Population cap for a window of the given size
+    pub fn max_dots(&self, window: Rect) -> usize {
+        if self.density_mode {
+            let megapixels = window.w() * window.h() / 1.0e6;
+            (megapixels * self.density) as usize
+        } else {
+            self.max_count.into()
+        }
+    }
+}
+
+/// Everything persisted to a config file: the scene plus each layer's settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub scene: Scene,
+    pub layers: Vec<Settings>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scene: Scene::default(),
+            layers: vec![Settings::default()],
+        }
+    }
+}
+
+impl Config {
+    /// Read a TOML config, filling anything missing from the defaults and
+    /// clamping anything out of range to the slider limits
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Reading config {}", path.display()))?;
+        let mut config: Config = toml::from_str(&text)
+            .with_context(|| format!("Parsing config {}", path.display()))?;
+
+        if config.layers.is_empty() {
+            config.layers.push(Settings::default());
+        }
+        config.scene.clamp_ranges();
+        config.layers.iter_mut().for_each(Settings::clamp_ranges);
+        Ok(config)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self)?;
+        fs::write(path, text)
+            .with_context(|| format!("Writing config {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(args: &[&str]) -> Opts {
+        Opts::from_iter(std::iter::once("nnndots").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn validate_rejects_no_dots() {
+        assert!(opts(&["--num-dots", "0"]).validate().is_err());
+        assert!(opts(&["--num-dots", "1"]).validate().is_ok());
+        // Past the slider range is only clamped
+        assert!(opts(&["--num-dots", "60000"]).validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_negative_speed_and_rate() {
+        for flag in ["--speed", "--rate"] {
+            assert!(opts(&[&format!("{}=-0.5", flag)]).validate().is_err(), "{} -0.5", flag);
+            assert!(opts(&[flag, "NaN"]).validate().is_err(), "{} NaN", flag);
+            assert!(opts(&[flag, "0"]).validate().is_ok(), "{} 0", flag);
+            assert!(opts(&[flag, "5000"]).validate().is_ok(), "{} 5000", flag);
+        }
+    }
+
+    #[test]
+    fn validate_checks_fps_and_window_size() {
+        assert!(opts(&["--fps", "0"]).validate().is_err());
+        assert!(opts(&["--fps", "inf"]).validate().is_err());
+        assert!(opts(&["--fps", "1000"]).validate().is_ok());
+
+        let (lo, hi) = (*WINDOW_RANGE.start(), *WINDOW_RANGE.end());
+        assert!(opts(&["--width", &lo.to_string()]).validate().is_ok());
+        assert!(opts(&["--width", &(lo - 1).to_string()]).validate().is_err());
+        assert!(opts(&["--height", &hi.to_string()]).validate().is_ok());
+        assert!(opts(&["--height", &(hi + 1).to_string()]).validate().is_err());
+    }
+
+    #[test]
+    fn defaults_are_within_ranges() {
+        let mut settings = Settings::default();
+        settings.clamp_ranges();
+        assert_eq!(serde_json::to_string(&settings).unwrap(), serde_json::to_string(&Settings::default()).unwrap());
+
+        let mut scene = Scene::default();
+        scene.clamp_ranges();
+        assert_eq!(serde_json::to_string(&scene).unwrap(), serde_json::to_string(&Scene::default()).unwrap());
+    }
+
+    #[test]
+    fn loaded_configs_are_clamped() {
+        let path = std::env::temp_dir().join(format!("nnndots-clamp-{}.toml", std::process::id()));
+        fs::write(&path, "[scene]\ntime_scale = 50.0\n\n[[layers]]\nbirth_age = -1.0\nbirth_repulsion = 10.0\nmax_speed = nan\n").unwrap();
+        let config = Config::load_from(&path);
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.scene.time_scale, *TIME_SCALE_RANGE.end());
+        assert_eq!(config.layers[0].birth_age, 0.0);
+        assert_eq!(config.layers[0].birth_repulsion, 10.0);
+        assert_eq!(config.layers[0].max_speed, *SPEED_RANGE.start());
+    }
+
+    #[test]
+    fn slow_growth_rates_survive_a_reload() {
+        assert!(GROWTH_PARAM_RANGE.contains(RATE_RANGE.start()) && GROWTH_PARAM_RANGE.contains(RATE_RANGE.end()));
+
+        let path = std::env::temp_dir().join(format!("nnndots-rate-{}.toml", std::process::id()));
+        let layer = Settings { growth_shape: 0.25, growth_scale: 0.5, ..Settings::default() };
+        Config { scene: Scene::default(), layers: vec![layer] }.save_to(&path).unwrap();
+        let config = Config::load_from(&path);
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.layers[0].growth_shape, 0.25);
+        assert_eq!(config.layers[0].growth_scale, 0.5);
+    }
+}
@@ -0,0 +1,656 @@
+//! Window-independent simulation core: advancing a population by one frame
+//! and sampling new dots. Nothing here touches egui or the window, so it can
+//! be driven headless or from tests with a fixed `Update`.
+
+use std::time::Duration;
+use log::*;
+use nannou::{rand::Rng, prelude::*};
+use rand_distr::{Distribution, Gamma, Normal, Uniform};
+use serde::{Deserialize, Serialize};
+
+use crate::color::{random_color, ColorExt, Rgba};
+use crate::dot::{Dot, Nannou, Phase, Point, RADIUS_LIMIT};
+use crate::grid::SpatialGrid;
+use crate::settings::{Settings, RATE_RANGE};
+
+/// Fastest turning of a wandering pivot's velocity in radians per second
+const MAX_PIVOT_TURN: f32 = 0.5;
+
+/// Smallest radius dots spawn at or grow to, so none vanish on their first frame
+const MIN_RADIUS: f32 = 1.0;
+
+/// Max radius used when the distribution parameters are invalid
+const FALLBACK_RADIUS: f32 = 200.0;
+
+/// Named distribution and parameters for the radius, picked from the settings window
+#[derive(Debug, Clone, Copy)]
+pub struct DistPreset {
+    pub name: &'static str,
+    pub dist: Dist,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl DistPreset {
+    /// Whether these are the given settings, allowing for slider rounding
+    pub fn matches(&self, dist: Dist, a: f32, b: f32) -> bool {
+        self.dist == dist && (self.a - a).abs() < 1e-3 && (self.b - b).abs() < 1e-3
+    }
+}
+
+/// Radius presets, the first being the defaults
+pub const RADIUS_PRESETS: &[DistPreset] = &[
+    DistPreset { name: "Default", dist: Dist::Gamma, a: 10.0, b: 10.0 },
+    DistPreset { name: "Tiny drizzle", dist: Dist::Gamma, a: 2.0, b: 4.0 },
+    DistPreset { name: "Big blobs", dist: Dist::Normal, a: 250.0, b: 40.0 },
+    DistPreset { name: "Long tail", dist: Dist::Gamma, a: 1.5, b: 80.0 },
+    DistPreset { name: "Even spread", dist: Dist::Uniform, a: 10.0, b: 300.0 },
+    DistPreset { name: "Uniform size", dist: Dist::Normal, a: 100.0, b: 2.0 },
+];
+
+/// Distribution that maximum radii or growth rates are sampled from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Dist {
+    #[default]
+    Gamma,
+    Normal,
+    Uniform,
+}
+
+impl Dist {
+    pub const ALL: [Dist; 3] = [Dist::Gamma, Dist::Normal, Dist::Uniform];
+
+    /// Slider labels for the `shape` and `scale` settings under this distribution
+    pub fn param_names(&self) -> (&'static str, &'static str) {
+        match self {
+            Dist::Gamma => ("Shape", "Scale"),
+            Dist::Normal => ("Mean", "Std Dev"),
+            Dist::Uniform => ("Min", "Max"),
+        }
+    }
+
+    /// Why the parameters don't form a valid distribution, if they don't
+    pub fn check(&self, a: f32, b: f32) -> Result<(), String> {
+        match self {
+            Dist::Gamma => Gamma::new(a, b).map(drop).map_err(|e| e.to_string()),
+            Dist::Normal if b > 0.0 => Normal::new(a, b).map(drop).map_err(|e| e.to_string()),
+            Dist::Normal => Err("standard deviation must be positive".into()),
+            Dist::Uniform if a <= b => Ok(()),
+            Dist::Uniform => Err("minimum must not exceed maximum".into()),
+        }
+    }
+
+    /// Probability density at `x`, or `None` if the parameters don't form a valid distribution
+    pub fn pdf(&self, x: f64, a: f32, b: f32) -> Option<f64> {
+        self.check(a, b).ok()?;
+        let (a, b) = (a as f64, b as f64);
+
+        let density = match self {
+            Dist::Gamma if x <= 0.0 => 0.0,
+            Dist::Gamma => ((a - 1.0) * x.ln() - x / b - ln_gamma(a) - a * b.ln()).exp(),
+            Dist::Normal => (-0.5 * ((x - a) / b).powi(2)).exp() / (b * (2.0 * std::f64::consts::PI).sqrt()),
+            Dist::Uniform if a == b => 0.0,
+            Dist::Uniform if (a..=b).contains(&x) => 1.0 / (b - a),
+            Dist::Uniform => 0.0,
+        };
+        Some(density)
+    }
+
+    /// Draw one value, or `None` if the parameters don't form a valid distribution
+    pub fn sample(&self, rng: &mut impl Rng, a: f32, b: f32) -> Option<f32> {
+        let sample = match self {
+            Dist::Gamma => Gamma::new(a, b).ok().map(|d| d.sample(rng)),
+            Dist::Normal if b > 0.0 => Normal::new(a, b).ok().map(|d| d.sample(rng)),
+            Dist::Uniform if a <= b => Some(Uniform::new_inclusive(a, b).sample(rng)),
+            _ => None,
+        };
+        sample.filter(|r| r.is_finite())
+    }
+}
+
+/// Distribution and parameter bits that last fell back
+type FallbackKey = (Dist, u32, u32);
+
+/// Parameters each sampled quantity last fell back with, so a bad combination
+/// is reported once rather than on every spawn, and again once it changes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fallbacks {
+    radius: Option<FallbackKey>,
+    growth: Option<FallbackKey>,
+}
+
+/// Whether these parameters differ from the ones that last fell back in `last`, recording them
+fn first_fallback(last: &mut Option<FallbackKey>, dist: Dist, a: f32, b: f32) -> bool {
+    let key = (dist, a.to_bits(), b.to_bits());
+    last.replace(key) != Some(key)
+}
+
+/// Draw from `dist`, or use `fallback` with a warning if it can't be sampled.
+///
+/// This covers parameters from sliders, configs and scenes alike.
+fn sample_or(rng: &mut impl Rng, name: &str, last: &mut Option<FallbackKey>, dist: Dist, a: f32, b: f32, fallback: f32) -> f32 {
+    dist.sample(rng, a, b).unwrap_or_else(|| {
+        if first_fallback(last, dist, a, b) {
+            let reason = dist.check(a, b).err().unwrap_or_else(|| "non-finite sample".into());
+            warn!("{} {:?}({}, {}) is invalid, using {}: {}", name, dist, a, b, fallback, reason);
+        }
+        fallback
+    })
+}
+
+/// Shortest TTL handed out, so no dot is culled on the frame it spawns
+pub const MIN_TTL: f32 = 0.1;
+
+/// How dot lifetimes are spread between their minimum and maximum
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TtlDist {
+    #[default]
+    Uniform,
+    /// Most dots die young, with a long tail reaching towards the maximum
+    Exponential,
+}
+
+impl TtlDist {
+    pub const ALL: [TtlDist; 2] = [TtlDist::Uniform, TtlDist::Exponential];
+
+    /// Lifetime in seconds within `lo..=hi`, never below `MIN_TTL`
+    pub fn sample(&self, rng: &mut impl Rng, lo: f32, hi: f32) -> f32 {
+        let lo = lo.max(MIN_TTL);
+        let span = hi - lo;
+        if span.is_nan() || span <= 0.0 {
+            return lo
+        }
+
+        let offset = match self {
+            TtlDist::Uniform => rng.gen_range(0.0..=span),
+            TtlDist::Exponential => {
+                // Inverse CDF of an exponential truncated to the span, with a mean near a quarter of it
+                let rate = 4.0 / span;
+                let u: f32 = rng.gen();
+                -(1.0 - u * (1.0 - (-rate * span).exp())).ln() / rate
+            }
+        };
+        (lo + offset).min(hi)
+    }
+}
+
+/// Natural log of the gamma function for `x > 0`, by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection keeps the series accurate for small arguments
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x)
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let series = COEFFICIENTS[1..].iter().enumerate()
+        .fold(COEFFICIENTS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Uniform sample that tolerates `lo >= hi`, which zeroed sliders produce
+fn random_range(rng: &mut impl Rng, lo: f32, hi: f32) -> f32 {
+    if lo < hi {
+        rng.gen_range(lo..hi)
+    } else {
+        lo
+    }
+}
+
+/// Orbital speed up to `max` either way, negative being clockwise. `bias` in
+/// `-1..=1` shifts the odds from all clockwise through even to all counterclockwise.
+///
+/// The sign and magnitude come from one uniform draw split at the odds of
+/// turning clockwise. With no bias that maps onto `-max..max` just as a plain
+/// uniform sample does, and the draw count is unchanged so seeded runs stay in step.
+fn biased_speed(rng: &mut impl Rng, max: f32, bias: f32) -> f32 {
+    if max <= 0.0 {
+        return -max
+    }
+
+    let clockwise = (1.0 - bias.clamp(-1.0, 1.0)) / 2.0;
+    let t = rng.gen_range(0.0..1.0);
+    if t < clockwise {
+        -max + t / clockwise * max
+    } else {
+        (t - clockwise) / (1.0 - clockwise) * max
+    }
+}
+
+/// Push dots younger than `cutoff` away from any dots they overlap.
+///
+/// The push is strongest at full overlap and fades to nothing at the rims,
+/// moving `strength` pixels per second at most.
+fn repel_newborns(dots: &mut [Dot], strength: f32, cutoff: Duration, delta: f32) {
+    let bodies = dots.iter().map(|d| (d.origin, d.extent())).collect::<Vec<_>>();
+    let grid = SpatialGrid::new(bodies.iter().copied());
+
+    for (i, dot) in dots.iter_mut().enumerate() {
+        if dot.age >= cutoff || dot.held() {
+            continue
+        }
+
+        let (origin, extent) = bodies[i];
+        let push = grid.query_neighbors(i).into_iter()
+            .map(|j| bodies[j])
+            .fold(Vec2::ZERO, |acc, (other, other_extent)| {
+                let reach = extent + other_extent;
+                let away = origin - other;
+                let dist = away.length();
+                if dist >= reach || reach <= 0.0 {
+                    acc
+                } else {
+                    acc + away.normalize_or_zero() * (1.0 - dist / reach)
+                }
+            });
+
+        dot.translate(push * strength * delta);
+    }
+}
+
+/// Merge each pair of overlapping dots into the larger of the two.
+///
+/// A dot merges at most once per frame.
+fn merge_overlapping(dots: &mut Vec<Dot>) {
+    let mergeable = |d: &Dot| d.phase == Phase::Growing && !d.clearing && !d.frozen && d.radius > 0.0;
+    let grid = SpatialGrid::new(dots.iter().map(|d| (d.origin, d.extent())));
+
+    let mut done = dots.iter().map(|d| !mergeable(d)).collect::<Vec<_>>();
+    let mut absorbed = vec![false; dots.len()];
+    for i in 0..dots.len() {
+        if done[i] {
+            continue
+        }
+
+        let partner = grid.query_neighbors(i).into_iter()
+            .find(|&j| !done[j]
+                  && dots[i].origin.distance(dots[j].origin) < dots[i].extent() + dots[j].extent());
+
+        if let Some(j) = partner {
+            let (keep, gone) = if dots[i].radius >= dots[j].radius { (i, j) } else { (j, i) };
+            let other = dots[gone];
+            dots[keep].absorb(&other);
+            done[i] = true;
+            done[j] = true;
+            absorbed[gone] = true;
+        }
+    }
+
+    let mut index = 0;
+    dots.retain(|_| {
+        index += 1;
+        !absorbed[index - 1]
+    });
+}
+
+/// Separation passes per frame when resolving collisions.
+///
+/// Pushing one pair apart can shove either into a third, so a single pass
+/// leaves crowds overlapping. More passes settle dense clusters but cost a
+/// grid rebuild each, and jitter remains when dots grow into each other faster
+/// than the passes can separate them.
+const COLLISION_ITERATIONS: usize = 4;
+
+/// Separate overlapping dots along their line of centers and exchange the
+/// approaching part of their velocities, weighting both by area as mass.
+///
+/// Only drift changes, so orbits carry on and dots are nudged off them over time.
+fn resolve_collisions(dots: &mut [Dot]) {
+    let solid = |d: &Dot| d.phase == Phase::Growing && !d.clearing && !d.held() && d.radius > 0.0;
+
+    for _ in 0..COLLISION_ITERATIONS {
+        let grid = SpatialGrid::new(dots.iter().map(|d| (d.origin, d.extent())));
+        let mut touching = false;
+
+        for i in 0..dots.len() {
+            for j in grid.query_neighbors(i).into_iter().filter(|&j| j > i) {
+                let (a, b) = (&dots[i], &dots[j]);
+                if !solid(a) || !solid(b) {
+                    continue
+                }
+
+                let between = b.origin - a.origin;
+                let dist = between.length();
+                let overlap = a.extent() + b.extent() - dist;
+                if overlap <= 0.0 || dist <= 0.0 {
+                    continue
+                }
+                touching = true;
+
+                let normal = between / dist;
+                let (ma, mb) = (a.radius.powi(2), b.radius.powi(2));
+                let (wa, wb) = (mb / (ma + mb), ma / (ma + mb));
+                let closing = (a.velocity() - b.velocity()).dot(normal);
+
+                dots[i].translate(-normal * overlap * wa);
+                dots[j].translate(normal * overlap * wb);
+                if closing > 0.0 {
+                    dots[i].drift -= 2.0 * wa * closing * normal;
+                    dots[j].drift += 2.0 * wb * closing * normal;
+                }
+            }
+        }
+
+        if !touching {
+            break
+        }
+    }
+}
+
+/// Why a dot is being removed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cause {
+    /// Reached its maximum radius and finished popping
+    Matured,
+    /// Ran out of TTL first
+    Expired,
+    /// Shrank away after a Clear
+    Cleared,
+}
+
+impl Cause {
+    /// Died on its own rather than being cleared, so it gets a pop effect
+    pub fn natural(&self) -> bool {
+        *self != Cause::Cleared
+    }
+}
+
+/// A dot removed this frame and why
+#[derive(Debug, Clone, Copy)]
+pub struct Death {
+    pub cause: Cause,
+    pub origin: Point,
+    pub color: Rgba,
+}
+
+/// Why a dot should be removed this frame, or `None` if it survives
+pub fn cause_of_death(dot: &Dot) -> Option<Cause> {
+    let dead = dot.ttl.is_zero() || dot.popped() || dot.radius <= 0.0;
+    if dot.held() || !dead {
+        None
+    } else if dot.clearing {
+        Some(Cause::Cleared)
+    } else if dot.popped() {
+        Some(Cause::Matured)
+    } else {
+        // Includes dots that never grew, e.g. with a zero maximum radius
+        Some(Cause::Expired)
+    }
+}
+
+/// Distance the attractor's pull is measured at, so `attraction` reads as an acceleration
+const ATTRACTION_REFERENCE: f32 = 100.0;
+
+/// Distances closer than this pull as hard as this, instead of without bound
+const MIN_ATTRACTION_DISTANCE: f32 = 10.0;
+
+/// Drift change this frame from a point pulling with `strength` pixels per second²
+/// at `ATTRACTION_REFERENCE`, falling off with inverse distance. Negative repels.
+fn attraction(dot: &Dot, point: Point, strength: f32, delta: f32) -> Vec2 {
+    let offset = point - dot.origin;
+    let distance = offset.length().max(MIN_ATTRACTION_DISTANCE);
+    offset.normalize_or_zero() * strength * ATTRACTION_REFERENCE / distance * delta
+}
+
+/// Advance every dot by one frame, then drop the dead ones. An `attractor`
+/// in world coordinates pulls on the dots by the settings' attraction.
+///
+/// Fills `deaths`, cleared first so its allocation can be kept between frames,
+/// with each removed dot and the cause, for counting and pop effects.
+pub fn step(dots: &mut Vec<Dot>, settings: &Settings, bounds: Rect, attractor: Option<Point>, update: &Update, deaths: &mut Vec<Death>) {
+    // Accelerate before moving so a change in gravity shows up the same frame
+    let delta = update.since_last.as_secs_f32();
+    let accel = vec2(0.0, -settings.gravity) * delta;
+    let attractor = attractor.filter(|_| settings.attraction != 0.0);
+    dots.iter_mut().for_each(|d| {
+        if d.held() {
+            // Optionally let frozen dots run down, to expire once released
+            if settings.frozen_aging {
+                d.ttl = d.ttl.saturating_sub(update.since_last);
+            }
+        } else {
+            d.drift += accel;
+            if let Some(point) = attractor {
+                d.drift += attraction(d, point, settings.attraction, delta);
+            }
+        }
+        d.update_with_bounds(update, bounds);
+    });
+
+    if settings.birth_repulsion > 0.0 {
+        let cutoff = Duration::from_secs_f32(settings.birth_age.max(0.0));
+        repel_newborns(dots, settings.birth_repulsion, cutoff, update.since_last.as_secs_f32());
+    }
+    if settings.merge {
+        merge_overlapping(dots);
+    }
+    if settings.collide {
+        resolve_collisions(dots);
+    }
+    dots.iter_mut()
+        .filter(|d| !d.held())
+        .for_each(|d| settings.arena.constrain(d, settings.arena_radius, bounds));
+
+    // Swap-remove compacts in place without shrinking capacity. It reorders the
+    // survivors, which is safe because draw order comes from `Dot::serial`.
+    deaths.clear();
+    let mut i = 0;
+    while i < dots.len() {
+        match cause_of_death(&dots[i]) {
+            Some(cause) => {
+                let d = dots.swap_remove(i);
+                deaths.push(Death { cause, origin: d.origin, color: d.color });
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// New dot at `origin` orbiting `pivot`, with everything else drawn from `rng`.
+///
+/// A non-empty `palette` supplies the color instead of random or tinted colors.
+/// Invalid distributions fall back, warning as recorded in `fallbacks`.
+pub fn sample_dot(settings: &Settings, palette: &[Rgba], fallbacks: &mut Fallbacks, rng: &mut impl Rng, origin: Point, pivot: Point) -> Dot {
+    let ttl = Duration::from_secs_f32(settings.ttl_dist.sample(rng, settings.ttl_min, settings.ttl_max));
+
+    // Coupled dots get more room to grow the longer they live
+    let max_radius: f32 = if settings.couple_ttl_radius {
+        ttl.as_secs_f32() * settings.ttl_coupling
+    } else {
+        sample_or(rng, "Radius", &mut fallbacks.radius, settings.radius_dist, settings.shape, settings.scale, FALLBACK_RADIUS)
+    };
+    let max_radius = max_radius.clamp(MIN_RADIUS, RADIUS_LIMIT);
+    let radius = (max_radius * settings.start_fraction).clamp(MIN_RADIUS, max_radius);
+
+    let growth_rate = sample_or(rng, "Growth", &mut fallbacks.growth, settings.growth_dist, settings.growth_shape, settings.growth_scale, settings.growth_scale)
+        .clamp(0.0, *RATE_RANGE.end());
+
+    let color = match palette.len() {
+        0 => random_color(rng, settings.tinted.then_some((settings.tint, settings.harmony)), &settings.channels),
+        n => palette[rng.gen_range(0..n)],
+    };
+
+    Dot::builder()
+        .color(color)
+        .origin(origin)
+        .pivot(pivot)
+        .radius(radius)
+        .max_radius(max_radius)
+        .easing(settings.easing)
+        .shape(settings.shapes.choose(rng))
+        .stroke_weight(settings.stroke_weight)
+        .stroke_color(settings.stroke.then(|| settings.stroke_color.with_alpha(255)))
+        .filled(settings.fill || !settings.stroke)
+        .style(settings.style())
+        .speed(biased_speed(rng, settings.max_speed, settings.orbit_bias))
+        .drift(vec2(random_range(rng, 0.0, settings.max_drift), 0.0).rotate(rng.gen_range(0.0..TAU)))
+        .pivot_velocity(vec2(random_range(rng, 0.0, settings.pivot_wander), 0.0).rotate(rng.gen_range(0.0..TAU)))
+        .pivot_turn(random_range(rng, -MAX_PIVOT_TURN, MAX_PIVOT_TURN))
+        .growth_rate(growth_rate)
+        .ttl(ttl)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, SeedableRng};
+
+    fn frame(secs: f32) -> Update {
+        Update { since_last: Duration::from_secs_f32(secs), since_start: Duration::ZERO }
+    }
+
+    fn still_dot(radius: f32, max_radius: f32, growth_rate: f32) -> Dot {
+        Dot::builder()
+            .color(rgba(0u8, 0, 0, 255))
+            .radius(radius)
+            .max_radius(max_radius)
+            .growth_rate(growth_rate)
+            .speed(0.0)
+            .build()
+    }
+
+    #[test]
+    fn step_grows_by_rate_times_delta() {
+        let settings = Settings::default();
+        let mut dots = vec![still_dot(10.0, 200.0, 50.0), still_dot(40.0, 100.0, 20.0)];
+        step(&mut dots, &settings, Rect::from_w_h(400.0, 400.0), None, &frame(0.5), &mut Vec::new());
+
+        assert!((dots[0].radius - 35.0).abs() < 1e-4);
+        assert!((dots[1].radius - 50.0).abs() < 1e-4);
+        assert_eq!(dots[0].age, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn step_removes_only_expired_dots() {
+        let settings = Settings::default();
+        let mut dots = vec![
+            Dot { ttl: Duration::from_millis(100), ..still_dot(10.0, 200.0, 1.0) },
+            Dot { ttl: Duration::from_secs(5), ..still_dot(20.0, 200.0, 1.0) },
+        ];
+        let mut deaths = Vec::new();
+        step(&mut dots, &settings, Rect::from_w_h(400.0, 400.0), None, &frame(0.2), &mut deaths);
+
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(dots.len(), 1);
+        assert_eq!(dots[0].start_radius, 20.0);
+    }
+
+    fn population(settings: &Settings, count: usize) -> Vec<Dot> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..count)
+            .map(|_| {
+                let origin = vec2(rng.gen_range(-100.0..100.0), rng.gen_range(-100.0..100.0));
+                sample_dot(settings, &[], &mut Fallbacks::default(), &mut rng, origin, Vec2::ZERO)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn negative_birth_age_does_not_panic() {
+        let settings = Settings { birth_age: -1.0, birth_repulsion: 50.0, ..Settings::default() };
+        let mut dots = population(&settings, 20);
+        step(&mut dots, &settings, Rect::from_w_h(400.0, 400.0), None, &frame(0.016), &mut Vec::new());
+    }
+
+    #[test]
+    fn large_delta_lands_on_max_radius() {
+        let settings = Settings {
+            ttl_min: 60.0,
+            ttl_max: 60.0,
+            growth_dist: Dist::Uniform,
+            growth_shape: 100.0,
+            growth_scale: 100.0,
+            ..Settings::default()
+        };
+        let mut dots = population(&settings, 50);
+        let mut deaths = Vec::new();
+        step(&mut dots, &settings, Rect::from_w_h(400.0, 400.0), None, &frame(30.0), &mut deaths);
+
+        assert!(deaths.is_empty());
+        assert_eq!(dots.len(), 50);
+        for dot in &dots {
+            assert_eq!(dot.radius, dot.max_radius);
+            assert!(dot.matured());
+        }
+    }
+
+    #[test]
+    fn zero_ttl_below_max_radius_is_expired() {
+        let settings = Settings::default();
+        let dot = Dot { ttl: Duration::ZERO, radius: 5.0, max_radius: 50.0, ..population(&settings, 1)[0] };
+        assert_eq!(cause_of_death(&dot), Some(Cause::Expired));
+
+        let mut dots = vec![dot];
+        let mut deaths = Vec::new();
+        step(&mut dots, &settings, Rect::from_w_h(400.0, 400.0), None, &frame(0.016), &mut deaths);
+        assert!(dots.is_empty());
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].cause, Cause::Expired);
+    }
+
+    #[test]
+    fn full_orbit_bias_turns_every_dot_one_way() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..10_000 {
+            assert!(biased_speed(&mut rng, 2.0, -1.0) <= 0.0);
+            assert!(biased_speed(&mut rng, 2.0, 1.0) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn neutral_orbit_bias_is_symmetric() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let n = 20_000;
+        let speeds = (0..n).map(|_| biased_speed(&mut rng, 2.0, 0.0)).collect::<Vec<_>>();
+        assert!(speeds.iter().all(|s| (-2.0..=2.0).contains(s)));
+
+        let clockwise = speeds.iter().filter(|&&s| s < 0.0).count() as f32 / n as f32;
+        assert!((clockwise - 0.5).abs() < 0.02, "{} clockwise", clockwise);
+    }
+
+    #[test]
+    fn invalid_distributions_fall_back() {
+        let settings = Settings {
+            radius_dist: Dist::Gamma,
+            shape: -1.0,
+            scale: 10.0,
+            growth_dist: Dist::Normal,
+            growth_shape: 50.0,
+            growth_scale: -3.0,
+            ..Settings::default()
+        };
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut fallbacks = Fallbacks::default();
+        for _ in 0..10 {
+            let dot = sample_dot(&settings, &[], &mut fallbacks, &mut rng, Vec2::ZERO, Vec2::ZERO);
+            assert_eq!(dot.max_radius, FALLBACK_RADIUS);
+            assert_eq!(dot.growth_rate, 0.0);
+            assert_eq!(dot.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn fallbacks_are_reported_when_parameters_change() {
+        let mut last = None;
+        assert!(first_fallback(&mut last, Dist::Uniform, 5.0, 1.0));
+        assert!(!first_fallback(&mut last, Dist::Uniform, 5.0, 1.0));
+        assert!(first_fallback(&mut last, Dist::Uniform, 6.0, 1.0));
+        assert!(first_fallback(&mut last, Dist::Uniform, 5.0, 1.0));
+        assert!(!first_fallback(&mut last, Dist::Uniform, 5.0, 1.0));
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::dot::Dot;
+use crate::settings::{Scene, Settings};
+
+/// Where scenes are saved and loaded when `--scene` isn't given
+pub const DEFAULT_PATH: &str = "bubbles-scene.json";
+
+/// Settings and live dots of one layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSnapshot {
+    pub settings: Settings,
+    pub dots: Vec<Dot>,
+}
+
+/// Everything needed to pick a scene back up where it was saved, unlike a
+/// config which only holds settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub scene: Scene,
+    pub layers: Vec<LayerSnapshot>,
+}
+
+impl Snapshot {
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Reading scene {}", path.display()))?;
+        let mut snapshot: Snapshot = serde_json::from_str(&text)
+            .with_context(|| format!("Parsing scene {}", path.display()))?;
+
+        if snapshot.layers.is_empty() {
+            bail!("Scene {} has no layers", path.display());
+        }
+        snapshot.scene.clamp_ranges();
+        snapshot.layers.iter_mut().for_each(|l| l.settings.clamp_ranges());
+        Ok(snapshot)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text)
+            .with_context(|| format!("Writing scene {}", path.display()))
+    }
+}
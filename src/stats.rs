@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use log::*;
+
+use crate::dot::{Dot, RADIUS_LIMIT};
+use crate::layer::Layer;
+use crate::settings::Settings;
+use crate::sim::{Cause, Death};
+
+/// Counts in equal buckets over a fixed range, so the edges stay put as the
+/// population changes and only the counts move
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    start: f64,
+    width: f64,
+    counts: Vec<u64>,
+}
+
+/// One bucket of a histogram, covering `start..end`
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub start: f64,
+    pub end: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Bucket `values` into `buckets` slices of `start..end`. Values past either
+    /// end count in the nearest bucket rather than being dropped.
+    pub fn new(start: f64, end: f64, buckets: usize, values: impl IntoIterator<Item = f64>) -> Self {
+        let buckets = buckets.max(1);
+        let width = if end > start { (end - start) / buckets as f64 } else { 1.0 };
+        let mut counts = vec![0; buckets];
+        for v in values.into_iter().filter(|v| !v.is_nan()) {
+            let i = ((v - start) / width).floor().clamp(0.0, (buckets - 1) as f64) as usize;
+            counts[i] += 1;
+        }
+        Histogram { start, width, counts }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = Bucket> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| {
+            let start = self.start + i as f64 * self.width;
+            Bucket { start, end: start + self.width, count }
+        })
+    }
+}
+
+/// Speeds from zero up to the top of the layer's speed range
+pub fn speed_histogram(dots: &[Dot], settings: &Settings) -> Histogram {
+    let top = if settings.max_speed > 0.0 { settings.max_speed } else { 1.0 };
+    Histogram::new(0.0, top as f64, settings.histogram_buckets.into(),
+                   dots.iter().map(|d| d.speed.abs() as f64))
+}
+
+/// Current radii from zero up to the largest radius a dot may have
+pub fn radius_histogram(dots: &[Dot], settings: &Settings) -> Histogram {
+    Histogram::new(0.0, RADIUS_LIMIT as f64, settings.histogram_buckets.into(),
+                   dots.iter().map(|d| d.radius as f64))
+}
+
+pub fn max_radius_histogram(dots: &[Dot], settings: &Settings) -> Histogram {
+    Histogram::new(0.0, RADIUS_LIMIT as f64, settings.histogram_buckets.into(),
+                   dots.iter().map(|d| d.max_radius as f64))
+}
+
+/// Running counts over a session, printed on exit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tally {
+    pub spawned: u64,
+    /// Died after reaching their maximum radius
+    pub popped: u64,
+    /// Ran out of TTL before maturing
+    pub expired: u64,
+    /// Sum of sampled maximum radii over every spawned dot
+    pub max_radius_total: f64,
+}
+
+impl std::ops::AddAssign for Tally {
+    fn add_assign(&mut self, other: Tally) {
+        self.spawned += other.spawned;
+        self.popped += other.popped;
+        self.expired += other.expired;
+        self.max_radius_total += other.max_radius_total;
+    }
+}
+
+impl Tally {
+    /// Count dots removed this frame
+    pub fn record(&mut self, deaths: &[Death]) {
+        for death in deaths {
+            match death.cause {
+                Cause::Matured => self.popped += 1,
+                Cause::Expired => self.expired += 1,
+                Cause::Cleared => {}
+            }
+        }
+    }
+
+    pub fn mean_max_radius(&self) -> f64 {
+        if self.spawned > 0 { self.max_radius_total / self.spawned as f64 } else { 0.0 }
+    }
+}
+
+/// Periodic `info!` summary of the population, every `interval` of the time fed to `tick`
+#[derive(Debug, Clone)]
+pub struct StatsLog {
+    interval: Duration,
+    elapsed: Duration,
+    /// Spawn total across layers at the last summary, for the spawn rate
+    spawned: u64,
+}
+
+impl StatsLog {
+    /// Summaries every `secs` seconds, with 0 or less disabling them
+    pub fn new(secs: f32) -> Self {
+        StatsLog {
+            interval: Duration::try_from_secs_f32(secs).unwrap_or_default(),
+            elapsed: Duration::ZERO,
+            spawned: 0,
+        }
+    }
+
+    /// Count `delta` towards the interval, logging once it has passed.
+    ///
+    /// Aggregates are only computed when a summary is due and would be shown.
+    pub fn tick(&mut self, delta: Duration, layers: &[Layer]) {
+        if self.interval.is_zero() {
+            return
+        }
+        self.elapsed += delta;
+        if self.elapsed < self.interval {
+            return
+        }
+
+        let spawned = layers.iter().map(|l| l.tally.spawned).sum::<u64>();
+        if log_enabled!(Level::Info) {
+            let dots = || layers.iter().flat_map(|l| l.dots.iter());
+            let count = dots().count();
+            let mean = |f: fn(&Dot) -> f32| match count {
+                0 => 0.0,
+                n => dots().map(f).sum::<f32>() / n as f32,
+            };
+            let rate = spawned.saturating_sub(self.spawned) as f32 / self.elapsed.as_secs_f32();
+            info!("{} dots, spawning {:.1}/s, mean radius {:.1}, mean speed {:.2}",
+                  count, rate, mean(|d| d.radius), mean(|d| d.speed.abs()));
+        }
+        self.spawned = spawned;
+        self.elapsed = Duration::ZERO;
+    }
+}
+
+/// Write every bucket of the speed and radius histograms, one row per bucket
+pub fn export_csv(dots: &[Dot], settings: &Settings, path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Creating {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    writeln!(out, "distribution,start,end,count")?;
+
+    let hists = [
+        ("speed", speed_histogram(dots, settings)),
+        ("radius", radius_histogram(dots, settings)),
+        ("max_radius", max_radius_histogram(dots, settings)),
+    ];
+    for (name, hist) in hists {
+        for b in hist.buckets() {
+            writeln!(out, "{},{},{},{}", name, b.start, b.end, b.count)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
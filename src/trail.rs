@@ -0,0 +1,60 @@
+use nannou::prelude::*;
+use nannou::draw::{Renderer, RendererBuilder};
+
+/// Offscreen copy of the scene that is faded rather than cleared each frame.
+///
+/// The window's own frame is redrawn from this texture before egui is painted
+/// over it, so bubbles leave trails but the UI never smears.
+pub struct Trail {
+    texture: wgpu::Texture,
+    renderer: Renderer,
+    reshaper: wgpu::TextureReshaper,
+}
+
+impl Trail {
+    pub fn new(frame: &Frame) -> Self {
+        let device = frame.device_queue_pair().device();
+        let texture = wgpu::TextureBuilder::new()
+            .size(frame.texture_size())
+            .sample_count(frame.texture_msaa_samples())
+            .format(Frame::TEXTURE_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .build(device);
+        let renderer = RendererBuilder::new()
+            .build_from_texture_descriptor(device, texture.descriptor());
+        let view = texture.view().build();
+        let reshaper = wgpu::TextureReshaper::new(
+            device,
+            &view,
+            texture.sample_count(),
+            texture.sample_type(),
+            frame.texture_msaa_samples(),
+            Frame::TEXTURE_FORMAT,
+        );
+
+        Trail { texture, renderer, reshaper }
+    }
+
+    /// Still matches the frame, which changes when the window is resized
+    pub fn fits(&self, frame: &Frame) -> bool {
+        self.texture.size() == frame.texture_size()
+            && self.texture.sample_count() == frame.texture_msaa_samples()
+    }
+
+    /// Draw over the accumulated scene, then copy it into the frame
+    pub fn render(&mut self, draw: &Draw, scale_factor: f32, frame: &Frame) {
+        let device = frame.device_queue_pair().device();
+        let view = self.texture.view().build();
+        let mut encoder = frame.command_encoder();
+        self.renderer.encode_render_pass(
+            device,
+            &mut encoder,
+            draw,
+            scale_factor,
+            self.texture.size(),
+            &view,
+            None,
+        );
+        self.reshaper.encode_render_pass(frame.texture_view(), &mut encoder);
+    }
+}
@@ -0,0 +1,741 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use nannou::prelude::*;
+use nannou::rand::{rngs::StdRng, SeedableRng};
+use nannou_egui::egui::{self, emath};
+use egui_plot::{Plot, Bar, BarChart, Line, PlotPoints};
+
+use crate::color::{self, ChannelRanges, Harmony, Rgb, parse_hex, to_hex};
+use crate::dot::{ArenaShape, ColorMode, Dot, Easing, Shape, SpawnRegion, RADIUS_LIMIT};
+use crate::history::History;
+use crate::keys;
+use crate::layer::Layer;
+use crate::quality::AutoQuality;
+use crate::settings::{
+    Scene, Settings, Theme, ARENA_RANGE, ATTRACTION_RANGE, BIAS_RANGE, BIRTH_AGE_RANGE, BUCKET_RANGE,
+    COUNT_RANGE, COUPLING_RANGE, COVERAGE_RANGE, DENSITY_RANGE, DRIFT_RANGE, EMIT_RATE_RANGE,
+    FPS_RANGE, FRACTION_RANGE, FRAME_MS_RANGE, GRAVITY_RANGE, GRID_SPACING_RANGE,
+    GROWTH_PARAM_RANGE, HUE_RATE_RANGE, PARTICLE_RANGE, QUALITY_RANGE, RADIUS_PARAM_RANGE,
+    REPULSION_RANGE, RETRIES_RANGE, SMOOTHING_RANGE, SPACING_RANGE, SPAWN_IN_RANGE,
+    SPAWN_PER_FRAME_RANGE, SPAWN_SCALE_RANGE, SPEED_RANGE, STRETCH_RANGE, STROKE_RANGE,
+    TIME_SCALE_RANGE, TRAIL_RANGE, TTL_RANGE,
+};
+use crate::sim::{Dist, TtlDist, RADIUS_PRESETS};
+use crate::stats;
+
+/// Buttons clicked in the settings window that the model has to act on
+#[derive(Debug, Default)]
+pub struct Actions {
+    pub dump: bool,
+    pub fill: bool,
+    pub export_csv: bool,
+    pub clear: bool,
+    /// Remove dots at once instead of letting them shrink away
+    pub clear_instantly: bool,
+    /// Restore the startup settings of the scene and active layer
+    pub reset: bool,
+    /// Undo any panning and zooming
+    pub reset_view: bool,
+    /// Write every layer's settings and dots to the scene file
+    pub save_scene: bool,
+    /// Replace the layers with those in the scene file
+    pub load_scene: bool,
+    pub toggle_help: bool,
+    /// Advance one frame while paused
+    pub step: bool,
+    /// Snapshot offset picked on the rewind slider
+    pub rewind: Option<usize>,
+}
+
+impl Theme {
+    /// Apply to `ctx` if it isn't already showing this theme
+    pub fn apply(&self, ctx: &egui::Context, scene: &Scene) {
+        let dark = self.dark(scene);
+        if ctx.style().visuals.dark_mode != dark {
+            ctx.set_visuals(if dark { egui::Visuals::dark() } else { egui::Visuals::light() });
+        }
+    }
+}
+
+/// Edit a nannou color in place with egui's color picker
+fn color_edit(ui: &mut egui::Ui, color: &mut Rgb) {
+    let mut c = to_color32(*color);
+    if egui::color_picker::color_edit_button_srgba(ui, &mut c, egui::color_picker::Alpha::Opaque).changed() {
+        *color = from_color32(c);
+    }
+}
+
+fn to_color32(c: Rgb) -> egui::Color32 {
+    egui::Color32::from_rgb(c.red, c.green, c.blue)
+}
+
+fn from_color32(c: egui::Color32) -> Rgb {
+    rgb(c.r(), c.g(), c.b())
+}
+
+/// Text field accepting a CSS color name or `#rrggbb`.
+///
+/// The typed text lives in egui memory while being edited, and `color` only
+/// changes once it names a valid color. Leaving the field shows `color` again
+/// if the text no longer matches it, e.g. after using the picker.
+fn color_name_edit(ui: &mut egui::Ui, id: &str, color: &mut Rgb) {
+    let parse = |text: &str| color::from_str(text.trim().to_lowercase().as_str()).or_else(|| parse_hex(text));
+
+    let id = egui::Id::new(id);
+    let mut text = ui.data_mut(|d| d.get_temp::<String>(id))
+        .unwrap_or_else(|| to_hex(*color));
+
+    let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(120.0));
+    let parsed = parse(&text);
+
+    if response.changed() {
+        if let Some(c) = parsed {
+            *color = c;
+        }
+        ui.data_mut(|d| d.insert_temp(id, text));
+    } else if !response.has_focus() && parsed != Some(*color) {
+        ui.data_mut(|d| d.remove::<String>(id));
+    }
+
+    if parsed.is_none() {
+        ui.colored_label(egui::Color32::LIGHT_RED, "Unknown color");
+    }
+}
+
+/// Fields for the low and high end of a channel range, kept in order
+fn channel_edit(ui: &mut egui::Ui, label: &str, [lo, hi]: &mut [u8; 2]) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(lo).clamp_range(0..=*hi));
+        ui.label("to");
+        ui.add(egui::DragValue::new(hi).clamp_range(*lo..=255));
+    });
+}
+
+/// Colors shown in the channel range preview
+const PREVIEW_SWATCHES: usize = 12;
+
+/// Row of sample colors from the current ranges and tint, drawn from a fixed
+/// seed so the row only changes when the settings do
+fn color_preview(ui: &mut egui::Ui, settings: &Settings) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let tint = settings.tinted.then_some((settings.tint, settings.harmony));
+
+    ui.horizontal(|ui| {
+        for _ in 0..PREVIEW_SWATCHES {
+            let c = color::random_color(&mut rng, tint, &settings.channels);
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+            ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgba_unmultiplied(c.red, c.green, c.blue, c.alpha));
+        }
+    });
+}
+
+/// Slider paired with a numeric field for typing exact values.
+/// Both are limited to `range`.
+fn slider<N: emath::Numeric>(ui: &mut egui::Ui, value: &mut N, range: RangeInclusive<N>, logarithmic: bool) {
+    let (lo, hi) = (range.start().to_f64(), range.end().to_f64());
+
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(value, range.clone())
+               .logarithmic(logarithmic)
+               .show_value(false));
+        ui.add(egui::DragValue::new(value)
+               .clamp_range(range)
+               .speed((hi - lo) / 500.0));
+    });
+
+    *value = N::from_f64(value.to_f64().clamp(lo, hi));
+}
+
+/// Distribution selector with sliders for its two parameters, labelled to suit it
+fn dist_edit(ui: &mut egui::Ui, id: &str, dist: &mut Dist, a: &mut f32, b: &mut f32, range: RangeInclusive<f32>) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(format!("{:?}", dist))
+        .show_ui(ui, |ui| {
+            for d in Dist::ALL {
+                ui.selectable_value(dist, d, format!("{:?}", d));
+            }
+        });
+
+    let (first, second) = dist.param_names();
+    ui.label(first);
+    slider(ui, a, range.clone(), true);
+
+    ui.label(second);
+    slider(ui, b, range, true);
+
+    if let Err(e) = dist.check(*a, *b) {
+        ui.colored_label(egui::Color32::RED, format!("Invalid {:?}: {}", dist, e));
+    }
+}
+
+/// Where the overlays are shown
+pub enum Parent<'a> {
+    /// A floating window for each, anchored to a corner
+    Windows(&'a egui::Context),
+    /// Collapsible sections stacked in one panel
+    Panel(&'a mut egui::Ui),
+}
+
+/// Show `add` as a window anchored at `anchor`, or a section of the panel
+fn section(parent: &mut Parent, title: &str, anchor: egui::Align2, add: impl FnOnce(&mut egui::Ui)) {
+    match parent {
+        Parent::Windows(ctx) => {
+            egui::Window::new(title)
+                .anchor(anchor, (0.0, 0.0))
+                .show(ctx, add);
+        }
+        Parent::Panel(ui) => {
+            // Give each section its own id scope, as separate windows would have
+            egui::CollapsingHeader::new(title)
+                .default_open(true)
+                .show(ui, |ui| ui.push_id(title, add));
+        }
+    }
+}
+
+/// Main controls window, editing the scene and the active layer
+pub fn settings_window(
+    parent: &mut Parent,
+    scene: &mut Scene,
+    layers: &mut Vec<Layer>,
+    active: &mut usize,
+    history: &History,
+    quality: &AutoQuality,
+) -> Actions {
+    let mut actions = Actions::default();
+
+    section(parent, "Settings", egui::Align2::LEFT_TOP, |ui| {
+        if scene.paused {
+            ui.heading(egui::RichText::new("PAUSED").color(egui::Color32::LIGHT_RED));
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            actions.dump = ui.button("Dump").clicked();
+            actions.export_csv = ui.button("Export CSV").clicked();
+            actions.fill = ui.button("Fill").clicked();
+
+            // Shift-click clears instantly, otherwise dots shrink away
+            actions.clear = ui.button("Clear").clicked();
+            actions.clear_instantly = ui.input(|i| i.modifiers.shift);
+
+            // Shift-click also clears the dots
+            actions.reset = ui.button("Reset").clicked();
+            actions.reset_view = ui.button("Reset view").clicked();
+            actions.save_scene = ui.button("Save scene").clicked();
+            actions.load_scene = ui.button("Load scene").clicked();
+            actions.toggle_help = ui.button("?").clicked();
+
+            let paused = scene.paused;
+            ui.toggle_value(&mut scene.paused, if paused {"Resume" } else {"Pause"});
+            if paused {
+                actions.step = ui.button("Step").clicked();
+            }
+        });
+
+        ui.label("Time Scale:");
+        slider(ui, &mut scene.time_scale, TIME_SCALE_RANGE, true);
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.pause_on_blur, "Pause on focus loss");
+            if scene.pause_on_blur {
+                ui.checkbox(&mut scene.resume_on_focus, "Resume on return");
+            }
+        });
+
+        if history.len() > 1 {
+            ui.label("Rewind (snapshots back):");
+            let mut offset = history.offset;
+            ui.add(egui::Slider::new(&mut offset, 0..=history.len() - 1));
+            if offset != history.offset {
+                actions.rewind = Some(offset);
+            }
+        }
+
+        egui::ComboBox::from_label("Theme")
+            .selected_text(format!("{:?}", scene.theme))
+            .show_ui(ui, |ui| {
+                for theme in Theme::ALL {
+                    ui.selectable_value(&mut scene.theme, theme, format!("{:?}", theme));
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.label("Background:");
+            color_edit(ui, &mut scene.bg_color);
+            color_name_edit(ui, "bg_color", &mut scene.bg_color);
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.gradient, "Gradient");
+            if scene.gradient {
+                color_edit(ui, &mut scene.bg_top);
+                color_edit(ui, &mut scene.bg_bottom);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.show_grid, "Grid");
+            ui.checkbox(&mut scene.show_scale_bar, "Scale bar");
+        });
+        if scene.show_grid || scene.show_scale_bar {
+            ui.label("Grid Spacing:");
+            slider(ui, &mut scene.grid_spacing, GRID_SPACING_RANGE, true);
+        }
+
+        ui.label("Trail Persistence:");
+        slider(ui, &mut scene.trail_persistence, TRAIL_RANGE, false);
+
+        ui.label("Emission Rate (dots/s):");
+        slider(ui, &mut scene.emit_rate, EMIT_RATE_RANGE, false);
+
+        // Vsync already caps the rate at the display's refresh, so only lower limits matter
+        ui.checkbox(&mut scene.limit_fps, "Limit FPS");
+        if scene.limit_fps {
+            slider(ui, &mut scene.max_fps, FPS_RANGE, false);
+        }
+
+        ui.checkbox(&mut scene.auto_quality, "Auto quality");
+        if scene.auto_quality {
+            ui.label("Target Frame Time (ms):");
+            slider(ui, &mut scene.target_frame_ms, FRAME_MS_RANGE, false);
+            ui.label(format!("Smoothed {:.1} ms, quality {:.0}%",
+                             quality.frame_time * 1000.0, quality.level * 100.0));
+        }
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.show_fps, "Show FPS");
+            ui.checkbox(&mut scene.show_population, "Show population");
+            ui.checkbox(&mut scene.side_panel, "Side panel");
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.status_title, "Status in title");
+            if scene.status_title {
+                ui.checkbox(&mut scene.title_coverage, "Coverage");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut scene.debug_vectors, "Debug vectors");
+            if scene.debug_vectors {
+                ui.checkbox(&mut scene.debug_orbits, "Orbits");
+            }
+        });
+
+        ui.add_space(16.0);
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("Layer")
+                .selected_text(format!("Layer {}", *active + 1))
+                .show_ui(ui, |ui| {
+                    for i in 0..layers.len() {
+                        ui.selectable_value(active, i, format!("Layer {}", i + 1));
+                    }
+                });
+
+            // New layers start from a copy of the active layer's settings
+            if ui.button("Add").clicked() {
+                let layer = layers[*active].fork();
+                layers.push(layer);
+                *active = layers.len() - 1;
+            }
+
+            if ui.add_enabled(layers.len() > 1, egui::Button::new("Remove")).clicked() {
+                layers.remove(*active);
+                *active = active.saturating_sub(1);
+            }
+        });
+
+        let (coverage, throttled) = (layers[*active].coverage(), layers[*active].throttled());
+        let palette_len = layers[*active].palette.len();
+        let settings = &mut layers[*active].settings;
+
+        ui.checkbox(&mut settings.batch_draw, "Batch draw");
+        if !settings.batch_draw {
+            ui.label("Ellipse Quality:");
+            slider(ui, &mut settings.ellipse_quality, QUALITY_RANGE, false);
+        }
+        ui.checkbox(&mut settings.depth_sort, "Draw small dots on top");
+
+        ui.checkbox(&mut settings.alpha_by_radius, "Fade with size");
+        if settings.alpha_by_radius {
+            slider(ui, &mut settings.alpha_strength, FRACTION_RANGE, false);
+        }
+
+        ui.checkbox(&mut settings.density_mode, "Scale with window");
+        if settings.density_mode {
+            ui.label("Dots per Megapixel:");
+            slider(ui, &mut settings.density, DENSITY_RANGE, false);
+        } else {
+            ui.label("Max Dots:");
+            slider(ui, &mut settings.max_count, COUNT_RANGE, true);
+        }
+
+        ui.label(format!("Coverage: {:.0}%{}", coverage * 100.0,
+                         if throttled { " (throttled)" } else { "" }));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.throttle_coverage, "Throttle above");
+            if settings.throttle_coverage {
+                slider(ui, &mut settings.max_coverage, COVERAGE_RANGE, false);
+            }
+        });
+
+        ui.checkbox(&mut settings.frozen_aging, "Frozen dots age");
+
+        ui.label("Count Smoothing (s):");
+        slider(ui, &mut settings.cap_smoothing, SMOOTHING_RANGE, false);
+
+        ui.label("Spawn per Frame:");
+        slider(ui, &mut settings.spawn_per_frame, SPAWN_PER_FRAME_RANGE, false);
+
+        egui::ComboBox::from_label("Spawn Region")
+            .selected_text(format!("{:?}", settings.spawn_region))
+            .show_ui(ui, |ui| {
+                for region in SpawnRegion::ALL {
+                    ui.selectable_value(&mut settings.spawn_region, region, format!("{:?}", region));
+                }
+            });
+        slider(ui, &mut settings.spawn_scale, SPAWN_SCALE_RANGE, false);
+        ui.label("Spawn Stretch (x, y):");
+        slider(ui, &mut settings.spawn_stretch_x, STRETCH_RANGE, true);
+        slider(ui, &mut settings.spawn_stretch_y, STRETCH_RANGE, true);
+
+        ui.label("Max Speed:");
+        slider(ui, &mut settings.max_speed, SPEED_RANGE, false);
+
+        ui.label("Orbit Direction (CW to CCW):");
+        slider(ui, &mut settings.orbit_bias, BIAS_RANGE, false);
+
+        ui.label("Max Drift:");
+        slider(ui, &mut settings.max_drift, DRIFT_RANGE, false);
+
+        ui.label("Pivot Wander:");
+        slider(ui, &mut settings.pivot_wander, DRIFT_RANGE, false);
+
+        ui.label("Cursor Attraction (negative repels):");
+        slider(ui, &mut settings.attraction, ATTRACTION_RANGE, false);
+
+        ui.label("Gravity:");
+        slider(ui, &mut settings.gravity, GRAVITY_RANGE, false);
+
+        ui.label("Growth Rate:");
+        dist_edit(ui, "Growth", &mut settings.growth_dist,
+                  &mut settings.growth_shape, &mut settings.growth_scale, GROWTH_PARAM_RANGE);
+
+        egui::ComboBox::from_label("Easing")
+            .selected_text(format!("{:?}", settings.easing))
+            .show_ui(ui, |ui| {
+                for easing in Easing::ALL {
+                    ui.selectable_value(&mut settings.easing, easing, format!("{:?}", easing));
+                }
+            });
+
+        egui::ComboBox::from_label("Color")
+            .selected_text(format!("{:?}", settings.color_mode))
+            .show_ui(ui, |ui| {
+                for mode in ColorMode::ALL {
+                    ui.selectable_value(&mut settings.color_mode, mode, format!("{:?}", mode));
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.tinted, "Tint");
+            if settings.tinted {
+                color_edit(ui, &mut settings.tint);
+                if ui.button("Randomize palette").clicked() {
+                    settings.tint = color::with_hue(settings.tint, nannou::rand::random_range(0.0, 360.0));
+                }
+            }
+        });
+
+        if palette_len > 0 {
+            ui.label(format!("Palette: {} colors", palette_len));
+        }
+
+        egui::CollapsingHeader::new("Channel ranges").show(ui, |ui| {
+            let channels = &mut settings.channels;
+            if !settings.tinted {
+                channel_edit(ui, "Red", &mut channels.red);
+                channel_edit(ui, "Green", &mut channels.green);
+                channel_edit(ui, "Blue", &mut channels.blue);
+            }
+            channel_edit(ui, "Alpha", &mut channels.alpha);
+            if ui.button("Default ranges").clicked() {
+                *channels = ChannelRanges::default();
+            }
+        });
+        color_preview(ui, settings);
+
+        if settings.tinted {
+            egui::ComboBox::from_label("Harmony")
+                .selected_text(format!("{:?}", settings.harmony))
+                .show_ui(ui, |ui| {
+                    for harmony in Harmony::ALL {
+                        ui.selectable_value(&mut settings.harmony, harmony, format!("{:?}", harmony));
+                    }
+                });
+        }
+
+        ui.label("Hue Cycle (degrees/s):");
+        slider(ui, &mut settings.hue_rate, HUE_RATE_RANGE, false);
+        if settings.hue_rate != 0.0 {
+            ui.checkbox(&mut settings.hue_new_only, "Only new dots");
+        }
+
+        egui::ComboBox::from_label("Shapes")
+            .selected_text(Shape::ALL.into_iter()
+                           .filter(|&s| settings.shapes.allows(s))
+                           .map(|s| format!("{:?}", s))
+                           .collect::<Vec<_>>()
+                           .join(", "))
+            .show_ui(ui, |ui| {
+                for shape in Shape::ALL {
+                    ui.checkbox(settings.shapes.allows_mut(shape), format!("{:?}", shape));
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.stroke, "Stroke");
+            if settings.stroke {
+                color_edit(ui, &mut settings.stroke_color);
+                ui.checkbox(&mut settings.fill, "Fill");
+            }
+        });
+        if settings.stroke {
+            ui.label("Stroke Weight:");
+            slider(ui, &mut settings.stroke_weight, STROKE_RANGE, false);
+        }
+
+        ui.label("Min Spacing:");
+        slider(ui, &mut settings.min_spacing, SPACING_RANGE, false);
+        if settings.min_spacing > 0.0 {
+            ui.label("Spacing Retries:");
+            slider(ui, &mut settings.spacing_retries, RETRIES_RANGE, false);
+        }
+
+        ui.label("Birth Repulsion:");
+        slider(ui, &mut settings.birth_repulsion, REPULSION_RANGE, false);
+
+        if settings.birth_repulsion > 0.0 {
+            ui.label("Birth Age:");
+            slider(ui, &mut settings.birth_age, BIRTH_AGE_RANGE, false);
+        }
+
+        ui.checkbox(&mut settings.merge, "Merge overlapping");
+        ui.checkbox(&mut settings.collide, "Collide");
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.pop_particles, "Pop particles");
+            if settings.pop_particles {
+                slider(ui, &mut settings.particle_count, PARTICLE_RANGE, false);
+            }
+        });
+
+        egui::ComboBox::from_label("Arena")
+            .selected_text(format!("{:?}", settings.arena))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.arena, ArenaShape::Rect, "Rect");
+                ui.selectable_value(&mut settings.arena, ArenaShape::Circle, "Circle");
+                ui.selectable_value(&mut settings.arena, ArenaShape::Wrap, "Wrap");
+                ui.selectable_value(&mut settings.arena, ArenaShape::Bounce, "Bounce");
+            });
+
+        if settings.arena == ArenaShape::Circle {
+            ui.label("Arena Radius:");
+            slider(ui, &mut settings.arena_radius, ARENA_RANGE, false);
+        }
+
+        ui.add_space(16.0);
+        ui.heading("Lifetime");
+
+        egui::ComboBox::from_id_source("TTL")
+            .selected_text(format!("{:?}", settings.ttl_dist))
+            .show_ui(ui, |ui| {
+                for d in TtlDist::ALL {
+                    ui.selectable_value(&mut settings.ttl_dist, d, format!("{:?}", d));
+                }
+            });
+        ui.label("Min TTL (s):");
+        slider(ui, &mut settings.ttl_min, TTL_RANGE, true);
+        ui.label("Max TTL (s):");
+        slider(ui, &mut settings.ttl_max, TTL_RANGE, true);
+        settings.ttl_max = settings.ttl_max.max(settings.ttl_min);
+
+        ui.add_space(16.0);
+        ui.heading("Radius Distribution");
+
+        // Shows Custom once the sliders move off every preset
+        let preset = RADIUS_PRESETS.iter()
+            .find(|p| p.matches(settings.radius_dist, settings.shape, settings.scale));
+        egui::ComboBox::from_label("Preset")
+            .selected_text(preset.map_or("Custom", |p| p.name))
+            .show_ui(ui, |ui| {
+                for p in RADIUS_PRESETS {
+                    if ui.selectable_label(preset.is_some_and(|s| s.name == p.name), p.name).clicked() {
+                        (settings.radius_dist, settings.shape, settings.scale) = (p.dist, p.a, p.b);
+                    }
+                }
+            });
+
+        dist_edit(ui, "Radius", &mut settings.radius_dist,
+                  &mut settings.shape, &mut settings.scale, RADIUS_PARAM_RANGE);
+
+        ui.checkbox(&mut settings.couple_ttl_radius, "Couple to TTL");
+        if settings.couple_ttl_radius {
+            ui.label("Pixels per second of TTL");
+            slider(ui, &mut settings.ttl_coupling, COUPLING_RANGE, false);
+        }
+
+        ui.label("Start Size (fraction of max):");
+        slider(ui, &mut settings.start_fraction, FRACTION_RANGE, false);
+
+        ui.label("Histogram Buckets:");
+        slider(ui, &mut settings.histogram_buckets, BUCKET_RANGE, false);
+
+        ui.label("Spawn-in Time (s):");
+        slider(ui, &mut settings.spawn_in, SPAWN_IN_RANGE, false);
+    });
+
+    actions
+}
+
+/// Instantaneous and average frame rate with a sparkline of recent frame times
+pub fn fps_window(parent: &mut Parent, frame_times: &VecDeque<f32>) {
+    let latest = frame_times.back().copied().unwrap_or_default();
+    let average = frame_times.iter().sum::<f32>() / frame_times.len().max(1) as f32;
+    let fps = |t: f32| if t > 0.0 { 1.0 / t } else { 0.0 };
+
+    section(parent, "FPS", egui::Align2::CENTER_TOP, |ui| {
+        ui.label(format!("{:.0} fps ({:.1} ms)", fps(latest), latest * 1000.0));
+        ui.label(format!("Average {:.0} fps ({:.1} ms)", fps(average), average * 1000.0));
+
+        let points = frame_times.iter().enumerate()
+            .map(|(i, &t)| [i as f64, t as f64 * 1000.0])
+            .collect::<PlotPoints>();
+        Plot::new("Frame times")
+            .height(60.0)
+            .width(200.0)
+            .include_y(0.0)
+            .show_axes([false, true])
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plt| plt.line(Line::new(points)));
+    });
+}
+
+/// Total bubble count over the recent past, scrolling with time
+pub fn population_window(parent: &mut Parent, population: &VecDeque<(f64, usize)>) {
+    section(parent, "Population", egui::Align2::CENTER_BOTTOM, |ui| {
+        let points = population.iter()
+            .map(|&(t, n)| [t, n as f64])
+            .collect::<PlotPoints>();
+        Plot::new("Count")
+            .view_aspect(2.5)
+            .include_y(0.0)
+            .y_axis_width(3)
+            .auto_bounds_x()
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plt| plt.line(Line::new(points).name("Bubbles")));
+    });
+}
+
+/// Keyboard shortcuts and what they do, closed with its X or the help key
+pub fn help_window(ctx: &egui::Context, open: &mut bool) {
+    egui::Window::new("Help")
+        .open(open)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, (0.0, 0.0))
+        .show(ctx, |ui| {
+            egui::Grid::new("Keys").striped(true).show(ui, |ui| {
+                for (key, command) in keys::BINDINGS {
+                    ui.monospace(format!("{:?}", key));
+                    ui.label(command.description());
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.label("Left click spawns and keeps emitting while held, or freezes the dot under the cursor.");
+            ui.label("Middle drag pans and the scroll wheel zooms.");
+            ui.label("Shift-click Clear or Reset to remove dots at once.");
+        });
+}
+
+/// Live stats of the dot under the cursor
+pub fn dot_tooltip(ctx: &egui::Context, dot: &Dot) {
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("Dot"), |ui| {
+        ui.label(format!("Radius: {:.1} / {:.1}", dot.radius, dot.max_radius));
+        ui.label(format!("Speed: {:.3} rad/s", dot.speed));
+        ui.label(format!("Growth: {:.1} px/s", dot.growth_rate));
+        ui.label(format!("TTL: {:.1} s", dot.ttl.as_secs_f32()));
+    });
+}
+
+pub fn speed_window(parent: &mut Parent, settings: &Settings, dots: &[Dot]) {
+    section(parent, "Speed", egui::Align2::RIGHT_TOP, |ui| {
+        Plot::new("Dist")
+            .view_aspect(1.5)
+            .include_x(0.0)
+            .include_x(settings.max_speed)
+            .include_y(20.0)
+            .y_axis_width(2)
+            .show(ui, |plt| {
+                let hist = stats::speed_histogram(dots, settings);
+                let chart1 = BarChart::new(bars(&hist))
+                    .name("Current");
+                plt.bar_chart(chart1);
+            });
+    });
+}
+
+/// Bars for each bucket, half as wide as the bucket so neighbours don't touch
+fn bars(hist: &stats::Histogram) -> Vec<Bar> {
+    hist.buckets()
+        .map(|b| Bar::new((b.start + b.end) / 2.0, b.count as f64).width(0.5 * (b.end - b.start)))
+        .collect()
+}
+
+/// Points along the overlaid density curve
+const PDF_SAMPLES: usize = 200;
+
+/// Current and maximum radius histograms, with the configured distribution's
+/// expected counts over the maximums. Buckets span the whole radius range so
+/// the axes hold still as the population changes.
+pub fn radius_window(parent: &mut Parent, settings: &Settings, dots: &[Dot]) {
+    let limit = RADIUS_LIMIT as f64;
+    section(parent, "Radius", egui::Align2::RIGHT_BOTTOM, |ui| {
+        Plot::new("Dist")
+            .legend(Default::default())
+            .view_aspect(1.5)
+            .include_x(0.0)
+            .include_x(limit)
+            .include_y(50.0)
+            .y_axis_width(2)
+            .show(ui, |plt| {
+                let hist = stats::radius_histogram(dots, settings);
+                let chart1 = BarChart::new(bars(&hist))
+                    .name("Current");
+                plt.bar_chart(chart1);
+
+                let hist = stats::max_radius_histogram(dots, settings);
+                let chart1 = BarChart::new(bars(&hist))
+                    .name("Maximum");
+                plt.bar_chart(chart1);
+
+                // Expected count per bucket is the density times bucket width times population
+                if !settings.couple_ttl_radius {
+                    let (dist, a, b) = (settings.radius_dist, settings.shape, settings.scale);
+                    let scale = hist.width() * dots.len() as f64;
+                    let points = (0..=PDF_SAMPLES)
+                        .filter_map(|i| {
+                            let x = limit * i as f64 / PDF_SAMPLES as f64;
+                            dist.pdf(x, a, b).map(|p| [x, p * scale])
+                        })
+                        .collect::<PlotPoints>();
+                    plt.line(Line::new(points).name(format!("{:?} PDF", dist)));
+                }
+            });
+    });
+}
@@ -0,0 +1,98 @@
+use nannou::prelude::*;
+
+use crate::dot::Point;
+
+/// Zoom limits, so the field can't collapse to a point or blow up past usefulness
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 10.0;
+
+/// Zoom factor per line of mouse wheel scrolling
+const ZOOM_STEP: f32 = 1.1;
+
+/// Pan and zoom applied to the bubble field, mapping world to screen
+/// coordinates as `world * zoom + offset`. The UI is not transformed.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub zoom: f32,
+    pub offset: Vec2,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View {
+            zoom: 1.0,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+impl View {
+    /// Drawing context in world coordinates
+    pub fn apply(&self, draw: &Draw) -> Draw {
+        draw.translate(self.offset.extend(0.0)).scale(self.zoom)
+    }
+
+    pub fn world_point(&self, screen: Point) -> Point {
+        (screen - self.offset) / self.zoom
+    }
+
+    /// Area of the world shown within `screen`
+    pub fn visible(&self, screen: Rect) -> Rect {
+        Rect::from_corners(self.world_point(screen.bottom_left()), self.world_point(screen.top_right()))
+    }
+
+    /// Zoom by `lines` of scrolling, keeping the world point under `screen` in place
+    pub fn zoom_at(&mut self, screen: Point, lines: f32) {
+        let anchor = self.world_point(screen);
+        self.zoom = (self.zoom * ZOOM_STEP.powf(lines)).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = screen - anchor * self.zoom;
+    }
+
+    pub fn pan(&mut self, by: Vec2) {
+        self.offset += by;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_point(view: &View, world: Point) -> Point {
+        world * view.zoom + view.offset
+    }
+
+    #[test]
+    fn world_point_inverts_the_view() {
+        let view = View { zoom: 2.5, offset: vec2(-40.0, 15.0) };
+        for world in [vec2(0.0, 0.0), vec2(100.0, -30.0), vec2(-7.5, 2.25)] {
+            assert!(view.world_point(screen_point(&view, world)).distance(world) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let mut view = View::default();
+        view.pan(vec2(30.0, -10.0));
+        let cursor = vec2(120.0, 80.0);
+        let under = view.world_point(cursor);
+
+        for lines in [3.0, -1.5, 100.0] {
+            view.zoom_at(cursor, lines);
+            assert!(view.world_point(cursor).distance(under) < 1e-3);
+        }
+        assert_eq!(view.zoom, MAX_ZOOM);
+    }
+
+    #[test]
+    fn visible_area_follows_pan_and_zoom() {
+        let mut view = View::default();
+        let screen = Rect::from_w_h(400.0, 200.0);
+        view.zoom_at(Point::ZERO, (2.0f32).ln() / ZOOM_STEP.ln());
+        view.pan(vec2(100.0, 0.0));
+
+        let area = view.visible(screen);
+        assert!((area.w() - 200.0).abs() < 1e-3);
+        assert!((area.h() - 100.0).abs() < 1e-3);
+        assert!(area.xy().distance(vec2(-50.0, 0.0)) < 1e-3);
+    }
+}